@@ -0,0 +1,74 @@
+// Nearest-palette lookup: PaletteMatcher's k-d tree against the plain linear
+// scan it replaced, over palette sizes and metrics.
+//
+//     cargo bench --bench palette_lookup
+
+use app_lib::color::{ColorMetric, PaletteMatcher};
+use app_lib::threshold::XorShift;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::Rgb;
+
+// Deterministic pseudo-random colours, so runs are comparable
+fn colors(count: usize, seed: u64) -> Vec<Rgb<u8>> {
+    let mut rng = XorShift(seed);
+    (0..count)
+        .map(|_| {
+            let [r, g, b, ..] = rng.next_u64().to_le_bytes();
+            Rgb([r, g, b])
+        })
+        .collect()
+}
+
+// The pre-k-d-tree lookup: one distance per palette colour
+fn linear_nearest(palette: &[Rgb<u8>], points: &[[f32; 3]], metric: ColorMetric, color: Rgb<u8>) -> Rgb<u8> {
+    let target = metric.to_space(color);
+    let mut min_dist = f32::MAX;
+    let mut best_color = palette[0];
+    for (p, point) in palette.iter().zip(points) {
+        let dist = metric.distance(target, *point);
+        if dist < min_dist {
+            min_dist = dist;
+            best_color = *p;
+        }
+    }
+    best_color
+}
+
+fn palette_lookup(c: &mut Criterion) {
+    // Far more distinct colours than the matcher's memo holds, so this measures
+    // the tree search itself rather than memo hits
+    let pixels = colors(256 * 256, 0x2545_F491_4F6C_DD1D);
+
+    for metric in [ColorMetric::Rgb, ColorMetric::Oklab, ColorMetric::Ciede2000] {
+        let mut group = c.benchmark_group(format!("nearest/{:?}", metric));
+        group.throughput(Throughput::Elements(pixels.len() as u64));
+        // The CIEDE2000 linear scan takes seconds per iteration
+        group.sample_size(10);
+
+        for size in [16, 64, 256] {
+            let palette = colors(size, 0x9E37_79B9_7F4A_7C15);
+            let points: Vec<[f32; 3]> = palette.iter().map(|&c| metric.to_space(c)).collect();
+            let matcher = PaletteMatcher::new(&palette, metric);
+
+            group.bench_with_input(BenchmarkId::new("linear", size), &pixels, |b, pixels| {
+                b.iter(|| {
+                    for &pixel in pixels {
+                        black_box(linear_nearest(&palette, &points, metric, pixel));
+                    }
+                })
+            });
+            group.bench_with_input(BenchmarkId::new("kd-tree", size), &pixels, |b, pixels| {
+                b.iter(|| {
+                    for &pixel in pixels {
+                        black_box(matcher.nearest(pixel));
+                    }
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, palette_lookup);
+criterion_main!(benches);
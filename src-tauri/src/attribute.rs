@@ -0,0 +1,468 @@
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::color::{ColorMetric, ColorTarget, PaletteMatcher};
+use crate::dithering::{DitheringConfig, apply_dithering, apply_dithering_with};
+
+// Refinement rounds when fitting NES sub-palettes to the attribute areas
+const SUB_PALETTE_ROUNDS: usize = 4;
+
+/// Per-cell colour limits of real 8-bit video hardware ("attribute clash").
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttributeMode {
+    #[default]
+    None,
+    ZXSpectrum,    // 2 colours (ink and paper) per 8x8 cell
+    C64Multicolor, // 4 colours per 4x8 cell, one of them the shared background
+    NES,           // one of 4 sub-palettes per 16x16 area, sharing the backdrop colour
+}
+
+// How a mode constrains the frame
+struct Layout {
+    cell_width: u32,
+    cell_height: u32,
+    // Colours every cell gets, picked once for the whole frame
+    shared: usize,
+    // Colours each cell picks on top of the shared ones
+    per_cell: usize,
+    // Cells choose between this many frame-wide sets of `per_cell` colours
+    // instead of picking their own
+    sub_palettes: Option<usize>,
+}
+
+impl AttributeMode {
+    fn layout(self) -> Option<Layout> {
+        match self {
+            AttributeMode::None => None,
+            AttributeMode::ZXSpectrum => Some(Layout {
+                cell_width: 8, cell_height: 8, shared: 0, per_cell: 2, sub_palettes: None,
+            }),
+            AttributeMode::C64Multicolor => Some(Layout {
+                cell_width: 4, cell_height: 8, shared: 1, per_cell: 3, sub_palettes: None,
+            }),
+            AttributeMode::NES => Some(Layout {
+                cell_width: 16, cell_height: 16, shared: 1, per_cell: 3, sub_palettes: Some(4),
+            }),
+        }
+    }
+}
+
+/// Quantizes `img` to `target` within the per-cell limits of `mode`: each cell
+/// gets the colours that represent it best, then the frame is dithered with
+/// every pixel restricted to its cell's colours.
+pub fn apply_attribute_clash(
+    img: &RgbImage,
+    target: &ColorTarget,
+    mode: AttributeMode,
+    config: &DitheringConfig
+) -> RgbImage {
+    let Some(layout) = mode.layout() else {
+        return apply_dithering(img, target, config);
+    };
+
+    let metric = config.color_metric;
+    let matcher = PaletteMatcher::for_target(target, metric);
+    let (width, height) = img.dimensions();
+    let cells_x = width.div_ceil(layout.cell_width);
+    let cells_y = height.div_ceil(layout.cell_height);
+
+    // Source colours of each cell, with how often they occur
+    let cells: Vec<Vec<Weighted>> = (0..cells_x * cells_y)
+        .into_par_iter()
+        .map(|cell| {
+            let (cx, cy) = (cell % cells_x, cell / cells_x);
+            let mut counts: HashMap<Rgb<u8>, u32> = HashMap::new();
+            for y in cy * layout.cell_height..((cy + 1) * layout.cell_height).min(height) {
+                for x in cx * layout.cell_width..((cx + 1) * layout.cell_width).min(width) {
+                    *counts.entry(*img.get_pixel(x, y)).or_default() += 1;
+                }
+            }
+            weighted(counts, metric)
+        })
+        .collect();
+
+    // The cell's colours as plain quantization would pick them: the candidates
+    // its restricted colours are chosen from
+    let candidates: Vec<Vec<Entry>> = cells.par_iter()
+        .map(|cell| {
+            let mut colors: Vec<Rgb<u8>> = cell.iter().map(|w| matcher.nearest(w.color)).collect();
+            colors.sort_by_key(|c| c.0);
+            colors.dedup();
+            colors.into_iter().map(|color| Entry::new(color, metric)).collect()
+        })
+        .collect();
+
+    // Shared colours: the ones most used by the frame as a whole
+    let mut usage: HashMap<Rgb<u8>, u32> = HashMap::new();
+    for cell in &cells {
+        for w in cell {
+            *usage.entry(matcher.nearest(w.color)).or_default() += w.count;
+        }
+    }
+    let mut by_usage: Vec<(Rgb<u8>, u32)> = usage.into_iter().collect();
+    by_usage.sort_by(|a, b| b.1.cmp(&a.1).then(a.0 .0.cmp(&b.0 .0)));
+    let shared: Vec<Entry> = by_usage.iter()
+        .take(layout.shared)
+        .map(|&(color, _)| Entry::new(color, metric))
+        .collect();
+
+    let cell_colors: Vec<Vec<Entry>> = match layout.sub_palettes {
+        None => cells.par_iter()
+            .zip(&candidates)
+            .map(|(cell, candidates)| {
+                let mut colors = shared.clone();
+                colors.extend(best_subset(cell, candidates, &shared, layout.per_cell, metric));
+                colors
+            })
+            .collect(),
+        Some(count) => {
+            let sub_palettes = fit_sub_palettes(&cells, &candidates, &shared, layout.per_cell, count, metric);
+            cells.par_iter()
+                .map(|cell| {
+                    let best = sub_palettes.iter()
+                        .min_by(|a, b| cell_error(cell, a, metric).total_cmp(&cell_error(cell, b, metric)))
+                        .expect("at least one sub-palette");
+                    best.clone()
+                })
+                .collect()
+        }
+    };
+
+    apply_dithering_with(img, config, |x, y, color| {
+        let cell = (y / layout.cell_height * cells_x + x / layout.cell_width) as usize;
+        nearest_entry(&cell_colors[cell], color, metric)
+    })
+}
+
+// A colour with its coordinates in metric space
+#[derive(Clone, Copy)]
+struct Entry {
+    color: Rgb<u8>,
+    point: [f32; 3],
+}
+
+impl Entry {
+    fn new(color: Rgb<u8>, metric: ColorMetric) -> Self {
+        Self { color, point: metric.to_space(color) }
+    }
+}
+
+// A source colour and how many pixels have it
+struct Weighted {
+    color: Rgb<u8>,
+    point: [f32; 3],
+    count: u32,
+}
+
+// Sorted by colour, so error sums don't depend on hash order
+fn weighted(counts: HashMap<Rgb<u8>, u32>, metric: ColorMetric) -> Vec<Weighted> {
+    let mut pixels: Vec<Weighted> = counts.into_iter()
+        .map(|(color, count)| Weighted { color, point: metric.to_space(color), count })
+        .collect();
+    pixels.sort_by_key(|w| w.color.0);
+    pixels
+}
+
+fn nearest_entry(entries: &[Entry], color: Rgb<u8>, metric: ColorMetric) -> Rgb<u8> {
+    let point = metric.to_space(color);
+    let mut best = (f32::MAX, color);
+    for entry in entries {
+        let dist = metric.distance(point, entry.point);
+        if dist < best.0 {
+            best = (dist, entry.color);
+        }
+    }
+    best.1
+}
+
+// Total distance of a cell's pixels to their closest colour in `entries`
+fn cell_error(cell: &[Weighted], entries: &[Entry], metric: ColorMetric) -> f32 {
+    cell.iter()
+        .map(|w| {
+            let closest = entries.iter()
+                .map(|e| metric.distance(w.point, e.point))
+                .fold(f32::MAX, f32::min);
+            closest * w.count as f32
+        })
+        .sum()
+}
+
+// Up to `k` of `candidates` that, together with `fixed`, leave `pixels` with
+// the smallest total error: chosen greedily, then improved by swapping single
+// colours for unused candidates while that helps.
+fn best_subset(
+    pixels: &[Weighted],
+    candidates: &[Entry],
+    fixed: &[Entry],
+    k: usize,
+    metric: ColorMetric
+) -> Vec<Entry> {
+    let fresh: Vec<Entry> = candidates.iter()
+        .filter(|c| !fixed.iter().any(|f| f.color == c.color))
+        .copied()
+        .collect();
+    if fresh.len() <= k {
+        return fresh;
+    }
+
+    let error_with = |chosen: &[Entry]| {
+        let mut entries = fixed.to_vec();
+        entries.extend_from_slice(chosen);
+        cell_error(pixels, &entries, metric)
+    };
+
+    let mut chosen: Vec<Entry> = Vec::with_capacity(k);
+    for _ in 0..k {
+        let next = fresh.iter()
+            .filter(|c| !chosen.iter().any(|e| e.color == c.color))
+            .min_by(|a, b| {
+                let with = |c: &Entry| {
+                    let mut trial = chosen.clone();
+                    trial.push(*c);
+                    error_with(&trial)
+                };
+                with(a).total_cmp(&with(b))
+            })
+            .copied()
+            .expect("more candidates than slots");
+        chosen.push(next);
+    }
+
+    let mut error = error_with(&chosen);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for slot in 0..k {
+            for candidate in &fresh {
+                if chosen.iter().any(|e| e.color == candidate.color) {
+                    continue;
+                }
+                let previous = std::mem::replace(&mut chosen[slot], *candidate);
+                let trial = error_with(&chosen);
+                if trial < error {
+                    error = trial;
+                    improved = true;
+                } else {
+                    chosen[slot] = previous;
+                }
+            }
+        }
+    }
+    chosen
+}
+
+// `count` frame-wide sets of `fixed` + `k` colours for cells to choose from,
+// like the NES's background sub-palettes. Starts from the sets the most common
+// cells would pick on their own, then alternates between assigning each cell
+// its best set and refitting each set to the pixels of its cells.
+fn fit_sub_palettes(
+    cells: &[Vec<Weighted>],
+    candidates: &[Vec<Entry>],
+    fixed: &[Entry],
+    k: usize,
+    count: usize,
+    metric: ColorMetric
+) -> Vec<Vec<Entry>> {
+    // Each cell's own choice, ranked by how many pixels would be happy with it
+    let own: Vec<Vec<Entry>> = cells.par_iter()
+        .zip(candidates)
+        .map(|(cell, candidates)| {
+            let mut colors = fixed.to_vec();
+            colors.extend(best_subset(cell, candidates, fixed, k, metric));
+            colors.sort_by_key(|e| e.color.0);
+            colors
+        })
+        .collect();
+    let mut seen: HashMap<Vec<[u8; 3]>, usize> = HashMap::new();
+    for (cell, colors) in cells.iter().zip(&own) {
+        let key = colors.iter().map(|e| e.color.0).collect();
+        *seen.entry(key).or_default() += cell.iter().map(|w| w.count as usize).sum::<usize>();
+    }
+    let mut ranked: Vec<(Vec<[u8; 3]>, usize)> = seen.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut sub_palettes: Vec<Vec<Entry>> = ranked.into_iter()
+        .take(count)
+        .map(|(colors, _)| colors.into_iter().map(|c| Entry::new(Rgb(c), metric)).collect())
+        .collect();
+    if sub_palettes.is_empty() {
+        return vec![fixed.to_vec()];
+    }
+
+    for _ in 0..SUB_PALETTE_ROUNDS {
+        let assignment: Vec<usize> = cells.par_iter()
+            .map(|cell| {
+                (0..sub_palettes.len())
+                    .min_by(|&a, &b| {
+                        cell_error(cell, &sub_palettes[a], metric)
+                            .total_cmp(&cell_error(cell, &sub_palettes[b], metric))
+                    })
+                    .expect("at least one sub-palette")
+            })
+            .collect();
+
+        // Refit against each set's cells, with their pixels merged by the
+        // colour plain quantization gives them to keep this cheap
+        sub_palettes = (0..sub_palettes.len())
+            .into_par_iter()
+            .map(|set| {
+                let mut merged: HashMap<Rgb<u8>, u32> = HashMap::new();
+                let mut pool: Vec<Entry> = Vec::new();
+                for (cell, _) in assignment.iter().enumerate().filter(|&(_, &a)| a == set) {
+                    for w in &cells[cell] {
+                        let nearest = nearest_entry(&candidates[cell], w.color, metric);
+                        *merged.entry(nearest).or_default() += w.count;
+                    }
+                    pool.extend_from_slice(&candidates[cell]);
+                }
+                if merged.is_empty() {
+                    return sub_palettes[set].clone();
+                }
+
+                pool.sort_by_key(|e| e.color.0);
+                pool.dedup_by_key(|e| e.color);
+                let mut colors = fixed.to_vec();
+                colors.extend(best_subset(&weighted(merged, metric), &pool, fixed, k, metric));
+                colors
+            })
+            .collect();
+    }
+    sub_palettes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::BitDepth;
+    use crate::dithering::DitheringAlgorithm;
+    use crate::palettes::{PaletteName, get_palette};
+    use crate::test_util::gradient;
+    use std::collections::BTreeSet;
+
+    // Not a multiple of any cell size, so the right and bottom cells are partial
+    const WIDTH: u32 = 43;
+    const HEIGHT: u32 = 29;
+
+    fn configs() -> [DitheringConfig; 3] {
+        [
+            DitheringConfig::default(),
+            DitheringConfig { dither_algorithm: DitheringAlgorithm::FloydSteinberg, ..Default::default() },
+            DitheringConfig { dither_algorithm: DitheringAlgorithm::Ordered, ..Default::default() },
+        ]
+    }
+
+    // The distinct colours of each cell, edge cells cut short by the image
+    fn cell_colors(img: &RgbImage, cell_width: u32, cell_height: u32) -> Vec<BTreeSet<[u8; 3]>> {
+        let (width, height) = img.dimensions();
+        let mut cells = Vec::new();
+        for cy in (0..height).step_by(cell_height as usize) {
+            for cx in (0..width).step_by(cell_width as usize) {
+                let mut colors = BTreeSet::new();
+                for y in cy..(cy + cell_height).min(height) {
+                    for x in cx..(cx + cell_width).min(width) {
+                        colors.insert(img.get_pixel(x, y).0);
+                    }
+                }
+                cells.push(colors);
+            }
+        }
+        cells
+    }
+
+    fn clash(img: &RgbImage, palette: PaletteName, mode: AttributeMode, config: &DitheringConfig) -> RgbImage {
+        let out = apply_attribute_clash(img, &ColorTarget::Palette(get_palette(palette)), mode, config);
+        assert_eq!(out.dimensions(), img.dimensions());
+        let colors = get_palette(palette);
+        assert!(out.pixels().all(|p| colors.contains(p)), "{:?}", mode);
+        out
+    }
+
+    // Some colour that, set aside, leaves every cell within `limit` colours
+    fn shared_background(cells: &[BTreeSet<[u8; 3]>], limit: usize) -> Option<[u8; 3]> {
+        let all: BTreeSet<[u8; 3]> = cells.iter().flatten().copied().collect();
+        all.into_iter().find(|bg| cells.iter().all(|c| c.iter().filter(|&c| c != bg).count() <= limit))
+    }
+
+    #[test]
+    fn zx_spectrum_cells_use_two_colours() {
+        let img = gradient(WIDTH, HEIGHT);
+        for config in configs() {
+            let out = clash(&img, PaletteName::ZXSpectrum, AttributeMode::ZXSpectrum, &config);
+            for cell in cell_colors(&out, 8, 8) {
+                assert!(cell.len() <= 2, "{:?}: {:?}", config.dither_algorithm, cell);
+            }
+        }
+    }
+
+    #[test]
+    fn c64_cells_add_three_colours_to_the_background() {
+        let img = gradient(WIDTH, HEIGHT);
+        for config in configs() {
+            let out = clash(&img, PaletteName::C64, AttributeMode::C64Multicolor, &config);
+            let cells = cell_colors(&out, 4, 8);
+            assert!(cells.iter().all(|c| c.len() <= 4), "{:?}", config.dither_algorithm);
+            assert!(shared_background(&cells, 3).is_some(), "{:?}: {:?}", config.dither_algorithm, cells);
+        }
+    }
+
+    #[test]
+    fn nes_areas_use_one_of_four_sub_palettes() {
+        let img = gradient(WIDTH, HEIGHT);
+        for config in configs() {
+            let out = clash(&img, PaletteName::NES, AttributeMode::NES, &config);
+            let cells = cell_colors(&out, 16, 16);
+            let backdrop = shared_background(&cells, 3)
+                .unwrap_or_else(|| panic!("{:?}: {:?}", config.dither_algorithm, cells));
+
+            // Each area's colours besides the backdrop fit in one of at most 4 sets of 3
+            let areas: Vec<BTreeSet<[u8; 3]>> = cells.into_iter()
+                .map(|mut c| { c.remove(&backdrop); c })
+                .collect();
+            let mut sub_palettes: Vec<&BTreeSet<[u8; 3]>> = areas.iter()
+                .filter(|a| !areas.iter().any(|b| a.len() < b.len() && a.is_subset(b)))
+                .collect();
+            sub_palettes.sort();
+            sub_palettes.dedup();
+            assert!(sub_palettes.len() <= 4, "{:?}: {:?}", config.dither_algorithm, sub_palettes);
+        }
+    }
+
+    #[test]
+    fn images_smaller_than_a_cell() {
+        let img = gradient(3, 5);
+        for (mode, palette, limit) in [
+            (AttributeMode::ZXSpectrum, PaletteName::ZXSpectrum, 2),
+            (AttributeMode::C64Multicolor, PaletteName::C64, 4),
+            (AttributeMode::NES, PaletteName::NES, 4),
+        ] {
+            let out = clash(&img, palette, mode, &DitheringConfig::default());
+            assert!(cell_colors(&out, 16, 16)[0].len() <= limit, "{:?}", mode);
+        }
+        let empty = RgbImage::new(0, 0);
+        assert!(clash(&empty, PaletteName::NES, AttributeMode::NES, &DitheringConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn grid_targets_are_limited_too() {
+        let img = gradient(WIDTH, HEIGHT);
+        let depth = BitDepth::uniform(2);
+        let levels = [0, 85, 170, 255];
+        for config in configs() {
+            let out = apply_attribute_clash(&img, &ColorTarget::Grid(depth), AttributeMode::ZXSpectrum, &config);
+            assert!(out.pixels().all(|p| p.0.iter().all(|v| levels.contains(v))));
+            for cell in cell_colors(&out, 8, 8) {
+                assert!(cell.len() <= 2, "{:?}: {:?}", config.dither_algorithm, cell);
+            }
+        }
+    }
+
+    #[test]
+    fn no_mode_is_plain_dithering() {
+        let img = gradient(WIDTH, HEIGHT);
+        let target = ColorTarget::Palette(get_palette(PaletteName::Pico8));
+        for config in configs() {
+            assert!(apply_attribute_clash(&img, &target, AttributeMode::None, &config) == apply_dithering(&img, &target, &config));
+        }
+    }
+}
@@ -0,0 +1,432 @@
+// Headless PixelForge: runs the same Pipeline as the desktop app on images,
+// folders of images and videos, without starting the Tauri window.
+
+use app_lib::attribute::AttributeMode;
+use app_lib::color::{BitDepth, ColorMetric};
+use app_lib::dithering::DitheringAlgorithm;
+use app_lib::effects::{CompositePreset, MaskType, ResampleFilter};
+use app_lib::palette_file::{PaletteFile, parse_hex_color};
+use app_lib::pipeline::{PaletteScope, Pipeline, PipelineConfig};
+use app_lib::quantize::PaletteAlgorithm;
+use app_lib::threshold::ThresholdMap;
+use app_lib::video::{self, ExportOptions};
+use clap::Parser;
+use image::ImageFormat;
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(name = "pixelforge", version, about = "Convert images and videos to 8-bit style without the GUI")]
+struct Cli {
+    /// Image, folder of images, or video to convert
+    input: PathBuf,
+
+    /// Output file, or output folder when INPUT is a folder
+    output: PathBuf,
+
+    /// Pipeline settings as JSON, in the desktop app's camelCase `ProcessingParams`
+    /// format. Flags below override it; anything neither sets keeps its default.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Downscale factor before quantization (0.05 - 1.0)
+    #[arg(long)]
+    scale: Option<f32>,
+
+    /// Palette size when --palette is None
+    #[arg(long)]
+    colors: Option<usize>,
+
+    /// Automatic palette algorithm (NeuQuant, MedianCut, KMeans, Octree, Wu)
+    #[arg(long)]
+    palette_algorithm: Option<String>,
+
+    /// Build one automatic palette for the whole video (or folder) from this
+    /// many sampled frames, instead of one per frame
+    #[arg(long)]
+    palette_samples: Option<usize>,
+
+    /// Colours every automatic palette must include, e.g. "#000000,FFCC00"
+    #[arg(long, value_delimiter = ',')]
+    pin_colors: Vec<String>,
+
+    /// Preset palette (e.g. GameBoy, NES, C64, ZXSpectrum, Pico8) or None for an extracted palette
+    #[arg(long)]
+    palette: Option<String>,
+
+    /// Palette file to use instead of --palette (.gpl, .txt, .pal, .act, .aco, .hex, .png)
+    #[arg(long)]
+    palette_file: Option<String>,
+
+    /// Snap to a hardware colour space instead of a palette: bits per channel
+    /// as R,G,B (e.g. 3,3,3 for the Mega Drive) or one number for all three
+    #[arg(long)]
+    bit_depth: Option<String>,
+
+    /// With --bit-depth: colours on screen at once (0 = the whole grid)
+    #[arg(long)]
+    grid_colors: Option<usize>,
+
+    /// Per-cell colour limits of 8-bit hardware (None, ZXSpectrum, C64Multicolor, NES)
+    #[arg(long)]
+    attribute_mode: Option<String>,
+
+    /// Dithering algorithm (None, Ordered, BlueNoise, FloydSteinberg, Atkinson, JarvisJudiceNinke,
+    /// Stucki, Burkes, Sierra, TwoRowSierra, SierraLite)
+    #[arg(long)]
+    dither: Option<String>,
+
+    /// Dithering strength (0.0 - 1.0)
+    #[arg(long)]
+    dither_strength: Option<f32>,
+
+    /// Alternate the scan direction every row for error diffusion
+    #[arg(long)]
+    serpentine: bool,
+
+    /// Bayer matrix size for Ordered dithering (2, 4, 8, 16, ...)
+    #[arg(long)]
+    bayer_size: Option<u32>,
+
+    /// Grayscale image used as a custom threshold map for Ordered dithering
+    #[arg(long)]
+    threshold_map: Option<String>,
+
+    /// Video: keep a pixel's previous dither decision while its colour changes
+    /// by at most this much per channel (0 - 255, 0 = off)
+    #[arg(long)]
+    temporal_threshold: Option<f32>,
+
+    /// Colour distance for palette matching (Rgb, Redmean, LinearRgb, Cie76, Ciede2000, Oklab)
+    #[arg(long)]
+    color_metric: Option<String>,
+
+    /// Composite video signal the frames go through (None, NTSC, PAL, RF)
+    #[arg(long)]
+    composite: Option<String>,
+
+    /// VHS: colour lagging to the right of the picture (0.0 - 1.0)
+    #[arg(long)]
+    chroma_shift: Option<f32>,
+
+    /// VHS: horizontal colour smear (0.0 - 1.0)
+    #[arg(long)]
+    chroma_blur: Option<f32>,
+
+    /// VHS: streaky luma noise (0.0 - 1.0)
+    #[arg(long)]
+    luma_noise: Option<f32>,
+
+    /// VHS: head-switching noise band at the bottom of the frame (0.0 - 1.0)
+    #[arg(long)]
+    tracking_noise: Option<f32>,
+
+    /// VHS: per-line horizontal jitter (0.0 - 1.0)
+    #[arg(long)]
+    tape_wobble: Option<f32>,
+
+    /// VHS: how often dropouts flash white streaks (0.0 - 1.0)
+    #[arg(long)]
+    dropouts: Option<f32>,
+
+    /// VHS: camcorder date stamp drawn in the corner (e.g. "JAN 1 1999")
+    #[arg(long)]
+    date_stamp: Option<String>,
+
+    /// Seed of the VHS noise; the same seed gives the same output
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// CRT scanline intensity (0.0 - 1.0)
+    #[arg(long)]
+    scanlines: Option<f32>,
+
+    /// Share of each scanline the beam lights (0.0 - 1.0)
+    #[arg(long)]
+    scanline_thickness: Option<f32>,
+
+    /// Softness of the beam edges (0.0 - 1.0)
+    #[arg(long)]
+    scanline_softness: Option<f32>,
+
+    /// Scanlines per low-resolution pixel row
+    #[arg(long)]
+    scanline_count: Option<u32>,
+
+    /// CRT curvature strength (0.0 - 1.0)
+    #[arg(long)]
+    curvature: Option<f32>,
+
+    /// Sampling of the curvature warp (Nearest, Bilinear, Bicubic)
+    #[arg(long)]
+    curvature_filter: Option<String>,
+
+    /// Rounding of the screen corners (0.0 - 1.0)
+    #[arg(long)]
+    corner_radius: Option<f32>,
+
+    /// Image drawn over the output, e.g. a TV frame with a transparent screen
+    #[arg(long)]
+    bezel: Option<String>,
+
+    /// CRT vignette strength (0.0 - 1.0)
+    #[arg(long)]
+    vignette: Option<f32>,
+
+    /// CRT phosphor mask (None, ApertureGrille, SlotMask, ShadowMask)
+    #[arg(long)]
+    mask: Option<String>,
+
+    /// CRT phosphor mask intensity (0.0 - 1.0)
+    #[arg(long)]
+    mask_intensity: Option<f32>,
+
+    /// How far bright pixels spread into the scanline gaps (0.0 - 1.0)
+    #[arg(long)]
+    beam_width: Option<f32>,
+
+    /// CRT bloom around bright areas (0.0 - 1.0)
+    #[arg(long)]
+    bloom: Option<f32>,
+
+    /// CRT halation, light scattered in the glass (0.0 - 1.0)
+    #[arg(long)]
+    halation: Option<f32>,
+
+    /// Blend CRT effects in linear light
+    #[arg(long)]
+    gamma_correct: bool,
+
+    /// Video playback speed (0.1 - 4.0)
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Interpolate video to this frame rate (0 = native)
+    #[arg(long, default_value_t = 0)]
+    interpolation_fps: u32,
+
+    /// FFmpeg executable used for videos
+    #[arg(long, env = "PIXELFORGE_FFMPEG", default_value = "ffmpeg")]
+    ffmpeg: String,
+}
+
+// Parses a CLI string the same way the frontend's strings are deserialized,
+// so the accepted names always match the serde variant names.
+fn parse_named<T: DeserializeOwned>(kind: &str, value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("Unknown {}: {}", kind, value))
+}
+
+// `value` parsed by `parse_named`, if the flag was given
+fn named<T: DeserializeOwned>(kind: &str, value: &Option<String>) -> Result<Option<T>, String> {
+    value.as_deref().map(|v| parse_named(kind, v)).transpose()
+}
+
+// Overwrites `field` with the flag's value, if it was given
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+fn load_config(path: &Path) -> Result<PipelineConfig, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text)
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+}
+
+impl Cli {
+    // The --config file (or the defaults) with every given flag applied on top
+    fn pipeline_config(&self) -> Result<PipelineConfig, String> {
+        let mut config = match &self.config {
+            Some(path) => load_config(path)?,
+            None => PipelineConfig::default(),
+        };
+
+        set(&mut config.scale_factor, self.scale);
+        set(&mut config.color_count, self.colors);
+        set(&mut config.palette_algorithm, named::<PaletteAlgorithm>("palette algorithm", &self.palette_algorithm)?);
+        if let Some(samples) = self.palette_samples {
+            config.palette_scope = PaletteScope::Video;
+            config.palette_sample_frames = samples;
+        }
+        if !self.pin_colors.is_empty() {
+            config.pinned_colors = self.pin_colors.iter()
+                .map(|hex| parse_hex_color(hex).ok_or_else(|| format!("Invalid colour: {} (expected RRGGBB)", hex)))
+                .collect::<Result<_, _>>()?;
+        }
+        set(&mut config.palette_name, self.palette.clone());
+        if let Some(path) = &self.palette_file {
+            config.custom_palette = Some(PaletteFile::load(path)?);
+        }
+        if let Some(bits) = &self.bit_depth {
+            config.bit_depth = Some(parse_bit_depth(bits)?);
+        }
+        set(&mut config.grid_colors, self.grid_colors);
+        set(&mut config.attribute_mode, named::<AttributeMode>("attribute mode", &self.attribute_mode)?);
+        if let Some(path) = &self.bezel {
+            config.bezel_image = Some(path.clone());
+        }
+
+        let dithering = &mut config.dithering;
+        set(&mut dithering.dither_algorithm, named::<DitheringAlgorithm>("dither algorithm", &self.dither)?);
+        set(&mut dithering.dither_strength, self.dither_strength);
+        dithering.serpentine |= self.serpentine;
+        set(&mut dithering.bayer_size, self.bayer_size);
+        if let Some(path) = &self.threshold_map {
+            dithering.threshold_map = Some(ThresholdMap::load(path)?);
+        }
+        set(&mut dithering.temporal_threshold, self.temporal_threshold);
+        set(&mut dithering.color_metric, named::<ColorMetric>("color metric", &self.color_metric)?);
+
+        let vhs = &mut config.vhs;
+        set(&mut vhs.chroma_shift, self.chroma_shift);
+        set(&mut vhs.chroma_blur, self.chroma_blur);
+        set(&mut vhs.luma_noise, self.luma_noise);
+        set(&mut vhs.tracking_noise, self.tracking_noise);
+        set(&mut vhs.tape_wobble, self.tape_wobble);
+        set(&mut vhs.dropouts, self.dropouts);
+        set(&mut vhs.date_stamp, self.date_stamp.clone());
+        set(&mut vhs.noise_seed, self.seed);
+
+        let effects = &mut config.effects;
+        set(&mut effects.composite, named::<CompositePreset>("composite preset", &self.composite)?);
+        set(&mut effects.scanline_intensity, self.scanlines);
+        set(&mut effects.scanline_thickness, self.scanline_thickness);
+        set(&mut effects.scanline_softness, self.scanline_softness);
+        set(&mut effects.scanline_count, self.scanline_count);
+        set(&mut effects.curvature_strength, self.curvature);
+        set(&mut effects.curvature_filter, named::<ResampleFilter>("curvature filter", &self.curvature_filter)?);
+        set(&mut effects.corner_radius, self.corner_radius);
+        set(&mut effects.vignette_strength, self.vignette);
+        set(&mut effects.mask_type, named::<MaskType>("mask type", &self.mask)?);
+        set(&mut effects.mask_intensity, self.mask_intensity);
+        set(&mut effects.beam_width, self.beam_width);
+        set(&mut effects.bloom_intensity, self.bloom);
+        set(&mut effects.halation_intensity, self.halation);
+        effects.gamma_correct |= self.gamma_correct;
+
+        Ok(config)
+    }
+}
+
+// "5,5,5", or "5" for the same depth on every channel
+fn parse_bit_depth(value: &str) -> Result<BitDepth, String> {
+    let bits = value.split(',')
+        .map(|b| b.trim().parse::<u8>().ok().filter(|b| (1..=8).contains(b)))
+        .collect::<Option<Vec<u8>>>();
+    match bits.as_deref() {
+        Some(&[bits]) => Ok(BitDepth::uniform(bits)),
+        Some(&[red, green, blue]) => Ok(BitDepth { red, green, blue }),
+        _ => Err(format!("Invalid bit depth: {} (expected R,G,B with 1 to 8 bits each)", value)),
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok()
+}
+
+fn convert_image(pipeline: &Pipeline, input: &Path, output: &Path) -> Result<(), String> {
+    let img = image::open(input)
+        .map_err(|e| format!("Failed to open {}: {}", input.display(), e))?
+        .to_rgb8();
+
+    pipeline.process(&img)
+        .save(output)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))
+}
+
+fn convert_folder(pipeline: &mut Pipeline, input: &Path, output: &Path) -> Result<(), String> {
+    let mut images: Vec<PathBuf> = std::fs::read_dir(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_image(path))
+        .collect();
+    images.sort();
+
+    // A "video" palette for a folder covers images sampled across all of it
+    if pipeline.needs_sampled_palette() && !images.is_empty() {
+        let count = pipeline.config().palette_sample_frames.clamp(1, images.len());
+        let frames = (0..count)
+            .map(|i| {
+                let path = &images[i * images.len() / count];
+                image::open(path)
+                    .map(|img| pipeline.downscale(&img.to_rgb8()))
+                    .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        pipeline.use_sampled_palette(&frames);
+    }
+    let pipeline = &*pipeline;
+
+    std::fs::create_dir_all(output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+
+    let errors: Vec<String> = images.par_iter()
+        .filter_map(|path| {
+            let target = output.join(path.file_name()?);
+            let result = convert_image(pipeline, path, &target);
+            if result.is_ok() {
+                eprintln!("{} -> {}", path.display(), target.display());
+            }
+            result.err()
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn convert_video(cli: &Cli, pipeline: &mut Pipeline) -> Result<(), String> {
+    let input = cli.input.to_string_lossy().to_string();
+    let info = video::probe_video(&cli.ffmpeg, &input)?;
+    video::prepare_sampled_palette(&cli.ffmpeg, &input, pipeline)?;
+
+    let options = ExportOptions {
+        input_video_path: input,
+        output_video_path: cli.output.to_string_lossy().to_string(),
+        width: info.width,
+        height: info.height,
+        total_duration_sec: info.duration_sec.unwrap_or(0.0),
+        video_speed: cli.speed,
+        interpolation_fps: cli.interpolation_fps,
+    };
+
+    video::export_video(&cli.ffmpeg, &options, pipeline, |progress| {
+        eprint!("\rExporting: {:5.1}%", progress);
+        let _ = std::io::stderr().flush();
+    })?;
+    eprintln!();
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<(), String> {
+    let mut pipeline = Pipeline::new(cli.pipeline_config()?)?;
+
+    if cli.input.is_dir() {
+        convert_folder(&mut pipeline, &cli.input, &cli.output)
+    } else if is_image(&cli.input) {
+        convert_image(&pipeline, &cli.input, &cli.output)
+    } else {
+        convert_video(cli, &mut pipeline)
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pixelforge: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,684 @@
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Palette entries per k-d tree leaf. Leaves are scanned linearly, so small
+// palettes end up as a single leaf and cost the same as a plain scan.
+const KD_LEAF_SIZE: usize = 8;
+
+// Distinct (palette, metric) trees kept around. Fixed palettes hit every frame;
+// per-frame auto palettes rarely repeat, so a handful is plenty.
+const MATCHER_CACHE_SIZE: usize = 8;
+
+// Slots in each tree's memo of recent lookups (a power of two). Neighbouring
+// pixels mostly repeat colours, so even CIEDE2000 rarely reaches the tree.
+const MEMO_BITS: u32 = 14;
+
+// Largest bit-depth grid searched through a k-d tree under perceptual metrics
+// (6 bits per channel). Finer grids use the 8 surrounding grid colours, which
+// is close enough when the grid is that dense.
+const MAX_GRID_TREE_BITS: u32 = 18;
+
+/// How "closest palette colour" is measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ColorMetric {
+    #[default]
+    Rgb,        // Squared distance in sRGB, fast but perceptually uneven
+    Redmean,    // sRGB weighted by the mean red level ("redmean" approximation)
+    LinearRgb,  // Squared distance in linear-light RGB
+    Cie76,      // CIELAB ΔE*76
+    Ciede2000,  // CIELAB ΔE*00, most accurate and slowest
+    Oklab,      // Squared distance in OKLab
+}
+
+// sRGB -> linear lookup, one entry per 8-bit level
+fn srgb_to_linear_lut() -> &'static [f32; 256] {
+    static LUT: OnceLock<[f32; 256]> = OnceLock::new();
+    LUT.get_or_init(|| {
+        let mut lut = [0.0; 256];
+        for (i, v) in lut.iter_mut().enumerate() {
+            let c = i as f32 / 255.0;
+            *v = if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        }
+        lut
+    })
+}
+
+/// Linear-light RGB in 0.0..1.0.
+pub fn srgb_to_linear(color: Rgb<u8>) -> [f32; 3] {
+    let lut = srgb_to_linear_lut();
+    [lut[color[0] as usize], lut[color[1] as usize], lut[color[2] as usize]]
+}
+
+/// Back from linear-light RGB in 0.0..1.0, clamping out-of-gamut values.
+pub fn linear_to_srgb(linear: [f32; 3]) -> Rgb<u8> {
+    Rgb(linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        let v = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (v * 255.0).round() as u8
+    }))
+}
+
+/// CIELAB (D65 white point).
+#[allow(clippy::excessive_precision)] // published matrix coefficients, kept verbatim
+pub fn srgb_to_lab(color: Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = srgb_to_linear(color);
+
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+    fn f(t: f32) -> f32 {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    }
+
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Back from CIELAB (D65 white point), clamping out-of-gamut values.
+#[allow(clippy::excessive_precision)]
+pub fn lab_to_srgb(lab: [f32; 3]) -> Rgb<u8> {
+    fn f_inv(t: f32) -> f32 {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA {
+            t * t * t
+        } else {
+            3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+        }
+    }
+
+    let fy = (lab[0] + 16.0) / 116.0;
+    let x = 0.95047 * f_inv(fy + lab[1] / 500.0);
+    let y = f_inv(fy);
+    let z = 1.08883 * f_inv(fy - lab[2] / 200.0);
+
+    linear_to_srgb([
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ])
+}
+
+/// OKLab (Björn Ottosson, 2020).
+#[allow(clippy::excessive_precision)]
+pub fn srgb_to_oklab(color: Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = srgb_to_linear(color);
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Back from OKLab, clamping out-of-gamut values.
+#[allow(clippy::excessive_precision)]
+pub fn oklab_to_srgb(lab: [f32; 3]) -> Rgb<u8> {
+    let [l, a, b] = lab;
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+    let (l, m, s) = (l_ * l_ * l_, m_ * m_ * m_, s_ * s_ * s_);
+
+    linear_to_srgb([
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ])
+}
+
+/// NTSC YIQ of an sRGB colour, on gamma-encoded values from 0.0 to 1.0.
+pub fn rgb_to_yiq(color: Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.596 * r - 0.274 * g - 0.322 * b,
+        0.211 * r - 0.523 * g + 0.312 * b,
+    ]
+}
+
+/// Back from YIQ, unclamped.
+pub fn yiq_to_rgb([y, i, q]: [f32; 3]) -> [f32; 3] {
+    [
+        y + 0.956 * i + 0.621 * q,
+        y - 0.272 * i - 0.647 * q,
+        y - 1.106 * i + 1.703 * q,
+    ]
+}
+
+/// PAL YUV of an sRGB colour, on gamma-encoded values from 0.0 to 1.0.
+pub fn rgb_to_yuv(color: Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.147 * r - 0.289 * g + 0.436 * b,
+        0.615 * r - 0.515 * g - 0.100 * b,
+    ]
+}
+
+/// Back from YUV, unclamped.
+pub fn yuv_to_rgb([y, u, v]: [f32; 3]) -> [f32; 3] {
+    [
+        y + 1.140 * v,
+        y - 0.395 * u - 0.581 * v,
+        y + 2.032 * u,
+    ]
+}
+
+// CIEDE2000 colour difference between two Lab colours (squared, like the
+// other metrics, so comparisons stay consistent)
+fn ciede2000(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = lab1.map(|v| v as f64);
+    let [l2, a2, b2] = lab2.map(|v| v as f64);
+
+    let c1 = (a1 * a1 + b1 * b1).sqrt();
+    let c2 = (a2 * a2 + b2 * b2).sqrt();
+    let c_mean = (c1 + c2) / 2.0;
+    let c_mean7 = c_mean.powi(7);
+    let g = 0.5 * (1.0 - (c_mean7 / (c_mean7 + 25f64.powi(7))).sqrt());
+
+    let a1p = a1 * (1.0 + g);
+    let a2p = a2 * (1.0 + g);
+    let c1p = (a1p * a1p + b1 * b1).sqrt();
+    let c2p = (a2p * a2p + b2 * b2).sqrt();
+
+    let hue = |b: f64, a: f64| {
+        if b == 0.0 && a == 0.0 {
+            0.0
+        } else {
+            let h = b.atan2(a).to_degrees();
+            if h < 0.0 { h + 360.0 } else { h }
+        }
+    };
+    let h1p = hue(b1, a1p);
+    let h2p = hue(b2, a2p);
+
+    let dl = l2 - l1;
+    let dc = c2p - c1p;
+    let dh = if c1p * c2p == 0.0 {
+        0.0
+    } else if (h2p - h1p).abs() <= 180.0 {
+        h2p - h1p
+    } else if h2p - h1p > 180.0 {
+        h2p - h1p - 360.0
+    } else {
+        h2p - h1p + 360.0
+    };
+    let dh_big = 2.0 * (c1p * c2p).sqrt() * (dh.to_radians() / 2.0).sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let cp_mean = (c1p + c2p) / 2.0;
+    let hp_mean = if c1p * c2p == 0.0 {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (hp_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * hp_mean).to_radians().cos()
+        + 0.32 * (3.0 * hp_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * hp_mean - 63.0).to_radians().cos();
+
+    let d_theta = 30.0 * (-((hp_mean - 275.0) / 25.0).powi(2)).exp();
+    let cp_mean7 = cp_mean.powi(7);
+    let r_c = 2.0 * (cp_mean7 / (cp_mean7 + 25f64.powi(7))).sqrt();
+    let l_term = (l_mean - 50.0).powi(2);
+    let s_l = 1.0 + 0.015 * l_term / (20.0 + l_term).sqrt();
+    let s_c = 1.0 + 0.045 * cp_mean;
+    let s_h = 1.0 + 0.015 * cp_mean * t;
+    let r_t = -(2.0 * d_theta).to_radians().sin() * r_c;
+
+    let dl_term = dl / s_l;
+    let dc_term = dc / s_c;
+    let dh_term = dh_big / s_h;
+
+    (dl_term * dl_term + dc_term * dc_term + dh_term * dh_term + r_t * dc_term * dh_term) as f32
+}
+
+// Cheap lower bound on `ciede2000`: the exact S_L and S_C without any of the
+// hue trigonometry. R_T is at most 2 sin 60° in magnitude and S_H <= S_C, so
+// the chroma/hue part is at least (1 - sin 60°) |Δ(a', b)|² / S_C².
+fn ciede2000_lower_bound(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+
+    let c_mean = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c_mean7 = c_mean.powi(7);
+    let g = 0.5 * (1.0 - (c_mean7 / (c_mean7 + 6_103_515_625.0)).sqrt());
+    let a1p = a1 * (1.0 + g);
+    let a2p = a2 * (1.0 + g);
+    let cp_mean = ((a1p * a1p + b1 * b1).sqrt() + (a2p * a2p + b2 * b2).sqrt()) / 2.0;
+
+    let l_term = ((l1 + l2) / 2.0 - 50.0).powi(2);
+    let s_l = 1.0 + 0.015 * l_term / (20.0 + l_term).sqrt();
+    let s_c = 1.0 + 0.045 * cp_mean;
+
+    let dl = (l2 - l1) / s_l;
+    let da = a2p - a1p;
+    let db = b2 - b1;
+    // Slack for rounding against the f64 evaluation
+    0.99 * (dl * dl + (1.0 - 0.866_026) * (da * da + db * db) / (s_c * s_c))
+}
+
+impl ColorMetric {
+    /// Coordinates of `color` in the space this metric measures in.
+    pub fn to_space(self, color: Rgb<u8>) -> [f32; 3] {
+        match self {
+            ColorMetric::Rgb | ColorMetric::Redmean => [color[0] as f32, color[1] as f32, color[2] as f32],
+            ColorMetric::LinearRgb => srgb_to_linear(color).map(|c| c * 255.0),
+            ColorMetric::Cie76 | ColorMetric::Ciede2000 => srgb_to_lab(color),
+            ColorMetric::Oklab => srgb_to_oklab(color),
+        }
+    }
+
+    /// Squared distance between two colours already converted by `to_space`.
+    pub fn distance(self, a: [f32; 3], b: [f32; 3]) -> f32 {
+        let d0 = a[0] - b[0];
+        let d1 = a[1] - b[1];
+        let d2 = a[2] - b[2];
+        match self {
+            ColorMetric::Redmean => {
+                let r_mean = (a[0] + b[0]) / 2.0;
+                (2.0 + r_mean / 256.0) * d0 * d0 + 4.0 * d1 * d1 + (2.0 + (255.0 - r_mean) / 256.0) * d2 * d2
+            }
+            ColorMetric::Ciede2000 => ciede2000(a, b),
+            _ => d0 * d0 + d1 * d1 + d2 * d2,
+        }
+    }
+}
+
+/// A hardware colour space with a fixed number of bits per channel, e.g. 3/3/3
+/// on the Mega Drive or 5/5/5 on the SNES. Each channel's levels are spread
+/// evenly over 0..=255.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BitDepth {
+    pub red: u8,   // 1 to 8
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Default for BitDepth {
+    fn default() -> Self {
+        Self::uniform(3)
+    }
+}
+
+impl BitDepth {
+    pub const fn uniform(bits: u8) -> Self {
+        Self { red: bits, green: bits, blue: bits }
+    }
+
+    fn bits(self) -> [u32; 3] {
+        [self.red, self.green, self.blue].map(|b| b.clamp(1, 8) as u32)
+    }
+
+    /// Every colour of the grid, 2^(red + green + blue) of them.
+    pub fn colors(self) -> Vec<Rgb<u8>> {
+        let [rb, gb, bb] = self.bits();
+        let mut colors = Vec::with_capacity(1 << (rb + gb + bb));
+        for r in 0..1 << rb {
+            for g in 0..1 << gb {
+                for b in 0..1 << bb {
+                    colors.push(Rgb([grid_level(rb, r), grid_level(gb, g), grid_level(bb, b)]));
+                }
+            }
+        }
+        colors
+    }
+
+    /// The closest grid colour, channel by channel.
+    pub fn snap(self, color: Rgb<u8>) -> Rgb<u8> {
+        let bits = self.bits();
+        Rgb(std::array::from_fn(|c| {
+            let [low, high] = grid_neighbours(bits[c], color[c]);
+            if color[c] - low <= high - color[c] { low } else { high }
+        }))
+    }
+
+    // Closest of the 8 grid colours around `color` under `metric`. That's the
+    // closest grid colour overall for metrics that measure each channel on
+    // its own.
+    fn nearest(self, color: Rgb<u8>, metric: ColorMetric) -> Rgb<u8> {
+        if metric == ColorMetric::Rgb {
+            return self.snap(color);
+        }
+
+        let bits = self.bits();
+        let [r, g, b] = std::array::from_fn::<_, 3, _>(|c| grid_neighbours(bits[c], color[c]));
+        let target = metric.to_space(color);
+        let mut best = (f32::MAX, color);
+        for r in r {
+            for g in g {
+                for b in b {
+                    let candidate = Rgb([r, g, b]);
+                    let dist = metric.distance(target, metric.to_space(candidate));
+                    if dist < best.0 {
+                        best = (dist, candidate);
+                    }
+                }
+            }
+        }
+        best.1
+    }
+}
+
+// 8-bit value of level `index` of a `bits`-bit channel
+fn grid_level(bits: u32, index: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    ((index * 255 + max / 2) / max) as u8
+}
+
+// The grid levels at or below and at or above `value`
+fn grid_neighbours(bits: u32, value: u8) -> [u8; 2] {
+    let max = (1 << bits) - 1;
+    let index = value as u32 * max / 255;
+    let low = grid_level(bits, index);
+    let high = if low < value { grid_level(bits, (index + 1).min(max)) } else { low };
+    [low, high]
+}
+
+/// What frames are quantized to: a list of colours, or every colour of a
+/// hardware colour space.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorTarget {
+    Palette(Vec<Rgb<u8>>),
+    Grid(BitDepth),
+}
+
+/// Nearest-colour search over a palette under a given metric. Lookups go
+/// through a memo of recent colours and then a k-d tree built in the metric's
+/// space, so a pixel costs roughly log(palette size) distance evaluations
+/// instead of one per palette colour. Trees are cached per palette and metric,
+/// so a video with a fixed palette builds its tree once.
+///
+/// A grid target usually needs no tree: colours are snapped to the grid
+/// directly.
+pub struct PaletteMatcher {
+    search: Search,
+}
+
+enum Search {
+    Palette(Arc<PaletteIndex>),
+    Grid(BitDepth, ColorMetric),
+}
+
+impl PaletteMatcher {
+    pub fn new(palette: &[Rgb<u8>], metric: ColorMetric) -> Self {
+        Self { search: Search::Palette(cached_index(palette, metric)) }
+    }
+
+    pub fn for_target(target: &ColorTarget, metric: ColorMetric) -> Self {
+        match target {
+            ColorTarget::Palette(palette) => Self::new(palette, metric),
+            ColorTarget::Grid(depth) => match metric {
+                ColorMetric::Rgb | ColorMetric::Redmean | ColorMetric::LinearRgb => {
+                    Self { search: Search::Grid(*depth, metric) }
+                }
+                // Lab and OKLab mix the channels, so the closest colour can lie
+                // further along the grid; search it like a palette
+                _ if depth.bits().iter().sum::<u32>() <= MAX_GRID_TREE_BITS => Self::new(&depth.colors(), metric),
+                _ => Self { search: Search::Grid(*depth, metric) },
+            },
+        }
+    }
+
+    pub fn nearest(&self, color: Rgb<u8>) -> Rgb<u8> {
+        match &self.search {
+            Search::Palette(index) => index.nearest(color),
+            Search::Grid(depth, metric) => depth.nearest(color, *metric),
+        }
+    }
+}
+
+// Recently used trees, most recent first
+fn cached_index(palette: &[Rgb<u8>], metric: ColorMetric) -> Arc<PaletteIndex> {
+    static CACHE: OnceLock<Mutex<VecDeque<Arc<PaletteIndex>>>> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some(pos) = cache.iter().position(|index| index.metric == metric && index.palette == palette) {
+        let index = cache.remove(pos).expect("position is in range");
+        cache.push_front(index.clone());
+        return index;
+    }
+
+    let index = Arc::new(PaletteIndex::new(palette, metric));
+    cache.push_front(index.clone());
+    cache.truncate(MATCHER_CACHE_SIZE);
+    index
+}
+
+enum KdNode {
+    Leaf { start: usize, end: usize },
+    Split { axis: usize, value: f32, left: usize, right: usize },
+}
+
+// k-d tree over the palette's coordinates in metric space. Entries are stored in
+// tree order as (palette index, coordinates); leaves refer to ranges of them.
+struct PaletteIndex {
+    palette: Vec<Rgb<u8>>,
+    metric: ColorMetric,
+    entries: Vec<(usize, [f32; 3])>,
+    nodes: Vec<KdNode>,
+    max_chroma: f32, // CIELAB chroma of the most saturated entry, for CIEDE2000 bounds
+    // Direct-mapped cache of past answers: bit 63 = filled, bits 32..56 = the
+    // packed input colour, low bits = palette index. Slots are independent
+    // atomics, so racing threads at worst overwrite each other's entries.
+    memo: Vec<AtomicU64>,
+}
+
+impl PaletteIndex {
+    fn new(palette: &[Rgb<u8>], metric: ColorMetric) -> Self {
+        let mut entries: Vec<(usize, [f32; 3])> = palette.iter()
+            .enumerate()
+            .map(|(i, &c)| (i, metric.to_space(c)))
+            .collect();
+        let max_chroma = entries.iter()
+            .map(|(_, p)| (p[1] * p[1] + p[2] * p[2]).sqrt())
+            .fold(0.0, f32::max);
+
+        let mut nodes = Vec::new();
+        if !entries.is_empty() {
+            let len = entries.len();
+            build_node(&mut entries, 0, len, &mut nodes);
+        }
+
+        let memo = (0..1usize << MEMO_BITS).map(|_| AtomicU64::new(0)).collect();
+
+        Self { palette: palette.to_vec(), metric, entries, nodes, max_chroma, memo }
+    }
+
+    fn nearest(&self, color: Rgb<u8>) -> Rgb<u8> {
+        if self.nodes.is_empty() {
+            return color;
+        }
+
+        let key = u32::from_be_bytes([0, color[0], color[1], color[2]]);
+        let slot = &self.memo[(key.wrapping_mul(0x9E37_79B1) >> (32 - MEMO_BITS)) as usize];
+        let cached = slot.load(Ordering::Relaxed);
+        if cached >> 63 == 1 && (cached >> 32) as u32 & 0xFF_FFFF == key {
+            return self.palette[cached as u32 as usize];
+        }
+
+        let target = self.metric.to_space(color);
+        let weights = self.bound_weights(target);
+        // (distance, palette index); ties go to the lower index, like a linear scan
+        let mut best = (f32::MAX, usize::MAX);
+        self.search(0, target, weights, &mut best);
+
+        slot.store(1 << 63 | (key as u64) << 32 | best.1 as u64, Ordering::Relaxed);
+        self.palette[best.1]
+    }
+
+    fn search(&self, node: usize, target: [f32; 3], weights: [f32; 3], best: &mut (f32, usize)) {
+        match self.nodes[node] {
+            KdNode::Leaf { start, end } => {
+                for &(i, point) in &self.entries[start..end] {
+                    if self.metric == ColorMetric::Ciede2000 && ciede2000_lower_bound(target, point) > best.0 {
+                        continue;
+                    }
+                    let dist = self.metric.distance(target, point);
+                    if dist < best.0 || (dist == best.0 && i < best.1) {
+                        *best = (dist, i);
+                    }
+                }
+            }
+            KdNode::Split { axis, value, left, right } => {
+                let diff = target[axis] - value;
+                let (near, far) = if diff < 0.0 { (left, right) } else { (right, left) };
+                self.search(near, target, weights, best);
+                // Every point beyond the split is at least this far away
+                if weights[axis] * diff * diff <= best.0 {
+                    self.search(far, target, weights, best);
+                }
+            }
+        }
+    }
+
+    // Per-axis factors w such that distance(a, b) >= w[i] * (a[i] - b[i])^2 for
+    // any palette entry, which is what lets the search skip whole subtrees
+    fn bound_weights(&self, target: [f32; 3]) -> [f32; 3] {
+        match self.metric {
+            ColorMetric::Rgb | ColorMetric::LinearRgb | ColorMetric::Cie76 | ColorMetric::Oklab => [1.0; 3],
+            // The redmean weights never drop below 2, 4 and 2
+            ColorMetric::Redmean => [2.0, 4.0, 2.0],
+            ColorMetric::Ciede2000 => {
+                // ΔE00² >= (ΔL / S_L)² with S_L <= 1.7475 over L in 0..100.
+                // The chroma/hue part is a quadratic form with |R_T| <= 2 sin 60°,
+                // so it is at least (1 - sin 60°) (Δa² + Δb²) / S_C², and S_C
+                // grows with the mean chroma, which a' inflates by at most 1.5x.
+                let target_chroma = (target[1] * target[1] + target[2] * target[2]).sqrt();
+                let s_c = 1.0 + 0.045 * 1.5 * (self.max_chroma + target_chroma) / 2.0;
+                let chroma_weight = (1.0 - 0.866_026) / (s_c * s_c);
+                // Slack for rounding, the bounds are evaluated in f32
+                [0.99 / (1.7475 * 1.7475), 0.99 * chroma_weight, 0.99 * chroma_weight]
+            }
+        }
+    }
+}
+
+// Builds the subtree for entries[start..end], splitting at the median of the
+// widest axis, and returns its node index
+fn build_node(entries: &mut [(usize, [f32; 3])], start: usize, end: usize, nodes: &mut Vec<KdNode>) -> usize {
+    let id = nodes.len();
+    if end - start <= KD_LEAF_SIZE {
+        nodes.push(KdNode::Leaf { start, end });
+        return id;
+    }
+
+    let slice = &mut entries[start..end];
+    let axis = (0..3)
+        .max_by(|&a, &b| spread(slice, a).total_cmp(&spread(slice, b)))
+        .unwrap_or(0);
+    let mid = slice.len() / 2;
+    slice.select_nth_unstable_by(mid, |a, b| a.1[axis].total_cmp(&b.1[axis]));
+    let value = slice[mid].1[axis];
+
+    // Placeholder, patched once the children exist
+    nodes.push(KdNode::Leaf { start, end });
+    let left = build_node(entries, start, start + mid, nodes);
+    let right = build_node(entries, start + mid, end, nodes);
+    nodes[id] = KdNode::Split { axis, value, left, right };
+    id
+}
+
+fn spread(entries: &[(usize, [f32; 3])], axis: usize) -> f32 {
+    let (min, max) = entries.iter().fold((f32::MAX, f32::MIN), |(min, max), (_, p)| {
+        (min.min(p[axis]), max.max(p[axis]))
+    });
+    max - min
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::random_color;
+    use crate::threshold::XorShift;
+
+    const METRICS: [ColorMetric; 6] = [
+        ColorMetric::Rgb,
+        ColorMetric::Redmean,
+        ColorMetric::LinearRgb,
+        ColorMetric::Cie76,
+        ColorMetric::Ciede2000,
+        ColorMetric::Oklab,
+    ];
+
+    // First palette entry with the smallest distance
+    fn linear_scan(palette: &[Rgb<u8>], metric: ColorMetric, color: Rgb<u8>) -> Rgb<u8> {
+        let target = metric.to_space(color);
+        let mut best = (f32::MAX, palette[0]);
+        for &c in palette {
+            let dist = metric.distance(target, metric.to_space(c));
+            if dist < best.0 {
+                best = (dist, c);
+            }
+        }
+        best.1
+    }
+
+    #[test]
+    fn matcher_agrees_with_linear_scan() {
+        let mut random = XorShift(0x2545_F491_4F6C_DD1D);
+        for metric in METRICS {
+            for size in [1, 5, 16, 40, 200] {
+                let mut palette: Vec<_> = (0..size).map(|_| random_color(&mut random)).collect();
+                // Duplicate entries, which tie exactly
+                let duplicates: Vec<_> = palette.iter().step_by(3).copied().collect();
+                palette.extend(duplicates);
+
+                let matcher = PaletteMatcher::new(&palette, metric);
+                for _ in 0..500 {
+                    let color = random_color(&mut random);
+                    let expected = linear_scan(&palette, metric, color);
+                    assert_eq!(matcher.nearest(color), expected, "{:?}, {} colours, {:?}", metric, size, color);
+                    // Second lookup comes from the memo
+                    assert_eq!(matcher.nearest(color), expected);
+                }
+                for &c in &palette {
+                    assert_eq!(matcher.nearest(c), c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_matcher_agrees_with_linear_scan() {
+        let mut random = XorShift(0x9E37_79B9_7F4A_7C15);
+        for depth in [BitDepth::uniform(1), BitDepth { red: 3, green: 3, blue: 2 }] {
+            let colors = depth.colors();
+            for metric in METRICS {
+                let matcher = PaletteMatcher::for_target(&ColorTarget::Grid(depth), metric);
+                for _ in 0..500 {
+                    let color = random_color(&mut random);
+                    let found = matcher.nearest(color);
+                    let expected = linear_scan(&colors, metric, color);
+                    let target = metric.to_space(color);
+                    assert_eq!(
+                        metric.distance(target, metric.to_space(found)),
+                        metric.distance(target, metric.to_space(expected)),
+                        "{:?}, {:?}, {:?}", depth, metric, color
+                    );
+                }
+            }
+        }
+    }
+}
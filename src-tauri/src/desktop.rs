@@ -0,0 +1,403 @@
+// The Tauri commands and app entry point, built with the `desktop` feature.
+
+// use std::path::Path;
+use image::{ImageFormat, DynamicImage, Rgb};
+use base64::{engine::general_purpose, Engine as _};
+use std::io::Cursor;
+use std::sync::Mutex;
+use tauri::path::BaseDirectory;
+use tauri::Manager;
+use crate::{palettes, quantize, video};
+use crate::dithering::TemporalState;
+use crate::palette_file::PaletteFile;
+use crate::palettes::{PaletteInfo, PaletteOrder};
+use crate::palette_library::{LibraryPalette, PaletteLibrary};
+use crate::color::ColorMetric;
+use crate::pipeline::{Pipeline, PipelineConfig};
+use crate::quantize::{ColorUsage, PaletteAlgorithm};
+use serde::Serialize;
+use crate::threshold::ThresholdMap;
+use crate::video::ExportOptions;
+use std::process::Command;
+use tauri::Emitter;
+
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+async fn extract_frame(app: tauri::AppHandle, video_path: String, frame_time_ms: u64) -> Result<String, String> {
+    let ffmpeg_str = resolve_ffmpeg_path(&app)?;
+
+    // Convert ms to seconds for -ss argument
+    let timestamp_secs = frame_time_ms as f64 / 1000.0;
+
+    let timestamp_str = timestamp_secs.to_string();
+
+    // Construct FFmpeg command to extract a single frame at the specific timestamp
+    // -ss: seek to position
+    // -i: input file
+    // -frames:v 1: extract 1 frame
+    // -c:v png: encode as PNG
+    // -f image2: force image format
+    // pipe:1: output to stdout
+    let args = vec![
+        "-ss", &timestamp_str,
+        "-i", &video_path,
+        "-frames:v", "1",
+        "-c:v", "png",
+        "-f", "image2",
+        "pipe:1"
+    ];
+
+    log::debug!("Executing FFmpeg extraction: {} {}", ffmpeg_str, args.join(" "));
+
+    let output = Command::new(&ffmpeg_str)
+        .args(&args)
+        .output()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("FFmpeg failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    // output.stdout contains the PNG image data
+    let buf = output.stdout;
+    
+    if buf.is_empty() {
+        return Err("FFmpeg returned empty output".to_string());
+    }
+
+    Ok(general_purpose::STANDARD.encode(&buf))
+}
+
+// Dither decisions of the last previewed frame, so scrubbing through a static
+// scene with temporal stability enabled doesn't shimmer
+#[derive(Default)]
+struct PreviewState(Mutex<TemporalState>);
+
+// The last video-wide palette the preview sampled, keyed by the video and the
+// settings it was built with, so it isn't rebuilt for every previewed frame
+#[derive(Default)]
+struct VideoPaletteCache(Mutex<Option<(String, Vec<Rgb<u8>>)>>);
+
+// `video_path` is the video the frame comes from, needed when the config asks
+// for one automatic palette over the whole video; `frame_index` is the frame's
+// position in it, which animates the composite signal's dot crawl
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn process_frame(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, PreviewState>,
+    palette_cache: tauri::State<'_, VideoPaletteCache>,
+    base64_image: String,
+    video_path: Option<String>,
+    frame_index: Option<u64>,
+    library: tauri::State<'_, PaletteLibraryState>,
+    mut config: PipelineConfig,
+) -> Result<String, String> {
+    library.0.resolve(&mut config)?;
+
+    let decoded_bytes = general_purpose::STANDARD.decode(&base64_image)
+        .map_err(|e| format!("Failed to decode base64 image: {}", e))?;
+
+    let img = image::load_from_memory(&decoded_bytes)
+        .map_err(|e| format!("Failed to load image from memory: {}", e))?
+        .to_rgb8();
+
+    let mut pipeline = Pipeline::new(config)?;
+    if let (true, Some(video_path)) = (pipeline.needs_sampled_palette(), video_path) {
+        let c = pipeline.config();
+        let key = format!(
+            "{}|{:?}|{}|{}|{:?}|{:?}|{}|{}",
+            video_path, c.palette_algorithm, c.color_count, c.palette_sample_frames,
+            c.pinned_colors, c.bit_depth, c.grid_colors, c.scale_factor
+        );
+        let mut cache = palette_cache.0.lock().map_err(|e| e.to_string())?;
+        match cache.as_ref() {
+            Some((cached_key, palette)) if *cached_key == key => pipeline.use_palette(palette.clone()),
+            _ => {
+                let ffmpeg_str = resolve_ffmpeg_path(&app)?;
+                video::prepare_sampled_palette(&ffmpeg_str, &video_path, &mut pipeline)?;
+                if let Some(palette) = pipeline.palette() {
+                    *cache = Some((key, palette.to_vec()));
+                }
+            }
+        }
+    }
+    let final_img = {
+        let mut temporal_state = state.0.lock().map_err(|e| e.to_string())?;
+        pipeline.process_temporal(&img, &mut temporal_state, frame_index.unwrap_or(0))
+    };
+
+    let mut buf = Vec::new();
+    let mut cursor = Cursor::new(&mut buf);
+    DynamicImage::ImageRgb8(final_img).write_to(&mut cursor, ImageFormat::Png)
+        .map_err(|e| format!("Failed to write processed image to buffer: {}", e))?;
+
+    Ok(general_purpose::STANDARD.encode(&buf))
+}
+
+fn resolve_ffmpeg_path(app: &tauri::AppHandle) -> Result<String, String> {
+    #[cfg(target_os = "windows")]
+    let binary_name = "resources/ffmpeg.exe";
+    #[cfg(not(target_os = "windows"))]
+    let binary_name = "resources/ffmpeg";
+
+    let ffmpeg_path = app.path().resolve(binary_name, BaseDirectory::Resource)
+        .map_err(|e| format!("Failed to resolve resource path: {}", e))?;
+    let ffmpeg_str = ffmpeg_path.to_string_lossy().to_string();
+
+    // Debug logging
+    match std::fs::metadata(&ffmpeg_path) {
+        Ok(meta) => log::debug!("Resolved FFmpeg path: '{}', Size: {} bytes", ffmpeg_str, meta.len()),
+        Err(e) => log::debug!("Resolved FFmpeg path: '{}', Error reading metadata: {}", ffmpeg_str, e),
+    }
+
+    Ok(ffmpeg_str)
+}
+
+// The built-in palettes, for the palette menu
+#[tauri::command]
+fn list_palettes() -> Vec<PaletteInfo> {
+    palettes::list_palettes()
+}
+
+// Loads a grayscale image as a custom ordered-dither threshold map, which the
+// frontend then passes back as `thresholdMap` in the PipelineConfig.
+#[tauri::command]
+fn load_threshold_map(path: String) -> Result<ThresholdMap, String> {
+    ThresholdMap::load(&path)
+}
+
+// Reads a palette file (.gpl, .txt, .pal, .act, .aco, .hex, .png); the frontend
+// passes its colours back as `customPalette` in the PipelineConfig.
+#[tauri::command]
+fn import_palette(path: String) -> Result<PaletteFile, String> {
+    PaletteFile::load(&path)
+}
+
+// Writes the palette `config` selects, in the format given by `path`'s extension
+#[tauri::command]
+fn export_palette(
+    library: tauri::State<'_, PaletteLibraryState>,
+    path: String,
+    name: String,
+    mut config: PipelineConfig,
+) -> Result<(), String> {
+    library.0.resolve(&mut config)?;
+    let pipeline = Pipeline::new(config)?;
+    let colors = pipeline.export_colors()
+        .ok_or("The automatic palette changes every frame; choose a palette to export")?;
+    PaletteFile { name, colors }.save(&path)
+}
+
+// Palette utilities. Each takes and returns whole palettes, so the frontend can
+// run them on a library palette and save the result with `edit_library_palette`.
+
+#[tauri::command]
+fn sort_palette(palette: PaletteFile, order: PaletteOrder) -> PaletteFile {
+    PaletteFile { colors: palettes::sort_palette(&palette.colors, order), ..palette }
+}
+
+#[tauri::command]
+fn dedupe_palette(palette: PaletteFile) -> PaletteFile {
+    PaletteFile { colors: palettes::dedupe_palette(&palette.colors), ..palette }
+}
+
+#[tauri::command]
+fn merge_palettes(name: String, palettes: Vec<PaletteFile>) -> PaletteFile {
+    let colors: Vec<_> = palettes.into_iter().map(|p| p.colors).collect();
+    PaletteFile { name, colors: palettes::merge_palettes(&colors) }
+}
+
+// The `count` entries of `palette` that best fit `source_path`: an image, or
+// `frame_count` frames sampled across a video
+#[tauri::command]
+async fn subset_palette(
+    app: tauri::AppHandle,
+    palette: PaletteFile,
+    source_path: String,
+    frame_count: usize,
+    count: usize,
+    color_metric: ColorMetric,
+) -> Result<PaletteFile, String> {
+    let frames = if ImageFormat::from_path(&source_path).is_ok() {
+        let img = image::open(&source_path)
+            .map_err(|e| format!("Failed to open {}: {}", source_path, e))?;
+        vec![img.thumbnail(PALETTE_SAMPLE_SIZE, PALETTE_SAMPLE_SIZE).to_rgb8()]
+    } else {
+        let ffmpeg_str = resolve_ffmpeg_path(&app)?;
+        video::sample_frames_within(&ffmpeg_str, &source_path, frame_count, PALETTE_SAMPLE_SIZE)?
+    };
+    let colors = palettes::subset_palette(&palette.colors, &frames, count, color_metric);
+    Ok(PaletteFile { colors, ..palette })
+}
+
+// `to`'s colours in `from`'s order, named after `to`
+#[tauri::command]
+fn remap_palette(from: PaletteFile, to: PaletteFile, order: PaletteOrder) -> PaletteFile {
+    PaletteFile { colors: palettes::remap_palette(&from.colors, &to.colors, order), ..to }
+}
+
+// Longest side of the frames `extract_palette` samples; plenty for a histogram
+const PALETTE_SAMPLE_SIZE: u32 = 512;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtractedPalette {
+    colors: Vec<ColorUsage>,
+    saved: Option<LibraryPalette>, // set when `save_as` was given
+}
+
+// Builds a `color_count` palette from `frame_count` frames sampled across the
+// video, most used colour first; with `save_as` it's also added to the library
+// under that name
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn extract_palette(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, PaletteLibraryState>,
+    video_path: String,
+    frame_count: usize,
+    color_count: usize,
+    algorithm: PaletteAlgorithm,
+    save_as: Option<String>,
+) -> Result<ExtractedPalette, String> {
+    let ffmpeg_str = resolve_ffmpeg_path(&app)?;
+    let frames = video::sample_frames_within(&ffmpeg_str, &video_path, frame_count, PALETTE_SAMPLE_SIZE)?;
+    let palette = quantize::extract_palette(&frames, color_count, algorithm);
+    let colors = quantize::palette_usage(&frames, &palette, ColorMetric::default());
+
+    let saved = match save_as {
+        Some(name) => Some(library.0.create(&name, colors.iter().map(|c| Rgb(c.color)).collect())?),
+        None => None,
+    };
+    Ok(ExtractedPalette { colors, saved })
+}
+
+// The user's palettes in <app data>/palettes; the frontend selects one by
+// passing its id as `paletteId` in the PipelineConfig
+struct PaletteLibraryState(PaletteLibrary);
+
+#[tauri::command]
+fn list_library_palettes(library: tauri::State<'_, PaletteLibraryState>) -> Result<Vec<LibraryPalette>, String> {
+    library.0.list()
+}
+
+#[tauri::command]
+fn create_library_palette(
+    library: tauri::State<'_, PaletteLibraryState>,
+    palette: PaletteFile,
+) -> Result<LibraryPalette, String> {
+    library.0.create(&palette.name, palette.colors)
+}
+
+#[tauri::command]
+fn rename_library_palette(
+    library: tauri::State<'_, PaletteLibraryState>,
+    id: String,
+    name: String,
+) -> Result<LibraryPalette, String> {
+    library.0.rename(&id, &name)
+}
+
+#[tauri::command]
+fn duplicate_library_palette(
+    library: tauri::State<'_, PaletteLibraryState>,
+    id: String,
+) -> Result<LibraryPalette, String> {
+    library.0.duplicate(&id)
+}
+
+#[tauri::command]
+fn delete_library_palette(library: tauri::State<'_, PaletteLibraryState>, id: String) -> Result<(), String> {
+    library.0.delete(&id)
+}
+
+// Replaces the colours of a library palette; `palette.name` is ignored
+#[tauri::command]
+fn edit_library_palette(
+    library: tauri::State<'_, PaletteLibraryState>,
+    id: String,
+    palette: PaletteFile,
+) -> Result<LibraryPalette, String> {
+    library.0.edit(&id, palette.colors)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_video(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, PaletteLibraryState>,
+    input_video_path: String,
+    output_video_path: String,
+    width: u32,
+    height: u32,
+    total_duration_sec: f64, 
+    video_speed: f64,
+    interpolation_fps: u32,
+    mut config: PipelineConfig,
+) -> Result<String, String> {
+    library.0.resolve(&mut config)?;
+    let mut pipeline = Pipeline::new(config)?;
+    let ffmpeg_str = resolve_ffmpeg_path(&app)?;
+    video::prepare_sampled_palette(&ffmpeg_str, &input_video_path, &mut pipeline)?;
+
+    let options = ExportOptions {
+        input_video_path,
+        output_video_path,
+        width,
+        height,
+        total_duration_sec,
+        video_speed,
+        interpolation_fps,
+    };
+
+    video::export_video(&ffmpeg_str, &options, &pipeline, |progress| {
+        let _ = app.emit("export-progress", progress);
+    })?;
+
+    Ok(format!("Export successful at {}", options.output_video_path))
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+  tauri::Builder::default()
+
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_fs::init())
+    .plugin(tauri_plugin_log::Builder::default().build())
+    .manage(PreviewState::default())
+    .manage(VideoPaletteCache::default())
+    .setup(|app| {
+        let dir = app.path().app_data_dir()?.join("palettes");
+        app.manage(PaletteLibraryState(PaletteLibrary::new(dir)));
+        Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+        greet, 
+        extract_frame, 
+        process_frame, 
+        load_threshold_map,
+        list_palettes,
+        import_palette,
+        export_palette,
+        extract_palette,
+        sort_palette,
+        dedupe_palette,
+        merge_palettes,
+        subset_palette,
+        remap_palette,
+        list_library_palettes,
+        create_library_palette,
+        rename_library_palette,
+        duplicate_library_palette,
+        delete_library_palette,
+        edit_library_palette,
+        export_video
+    ])
+    .run(tauri::generate_context!())
+    .expect("error while running tauri application");
+}
@@ -1,150 +1,612 @@
 use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
 
+use crate::color::{ColorMetric, ColorTarget, PaletteMatcher};
+use crate::threshold::{ThresholdMap, blue_noise};
+
+// Columns per error-diffusion wavefront cell. Wide enough that scheduling a
+// cell costs little next to dithering it; narrow enough that a 1080p row
+// spreads over many cores.
+const WAVEFRONT_BLOCK: u32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DitheringAlgorithm {
     None,
-    Ordered,        // Bayer 4x4
+    Ordered,        // Bayer matrix or custom threshold map
+    BlueNoise,      // Void-and-cluster threshold map
+    // Error diffusion, see DiffusionKernel
     FloydSteinberg,
+    Atkinson,
+    JarvisJudiceNinke,
+    Stucki,
+    Burkes,
+    Sierra,
+    TwoRowSierra,
+    SierraLite,
+}
+
+/// An error-diffusion kernel: each tap pushes `weight / divisor` of the
+/// quantization error to the pixel at (x + dx, y + dy).
+pub struct DiffusionKernel {
+    pub divisor: f32,
+    pub taps: &'static [(i32, i32, f32)],
 }
 
-// 4x4 Bayer Matrix
-const BAYER_MATRIX: [u8; 16] = [
-    0,  8,  2, 10,
-    12, 4, 14,  6,
-    3, 11,  1,  9,
-    15, 7, 13,  5,
-];
+const FLOYD_STEINBERG: DiffusionKernel = DiffusionKernel {
+    divisor: 16.0,
+    taps: &[
+                              (1, 0, 7.0),
+        (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0),
+    ],
+};
+
+// Only diffuses 6/8 of the error, which keeps highlights and shadows clean
+const ATKINSON: DiffusionKernel = DiffusionKernel {
+    divisor: 8.0,
+    taps: &[
+                             (1, 0, 1.0), (2, 0, 1.0),
+        (-1, 1, 1.0), (0, 1, 1.0), (1, 1, 1.0),
+                      (0, 2, 1.0),
+    ],
+};
+
+const JARVIS_JUDICE_NINKE: DiffusionKernel = DiffusionKernel {
+    divisor: 48.0,
+    taps: &[
+                                                (1, 0, 7.0), (2, 0, 5.0),
+        (-2, 1, 3.0), (-1, 1, 5.0), (0, 1, 7.0), (1, 1, 5.0), (2, 1, 3.0),
+        (-2, 2, 1.0), (-1, 2, 3.0), (0, 2, 5.0), (1, 2, 3.0), (2, 2, 1.0),
+    ],
+};
+
+const STUCKI: DiffusionKernel = DiffusionKernel {
+    divisor: 42.0,
+    taps: &[
+                                                (1, 0, 8.0), (2, 0, 4.0),
+        (-2, 1, 2.0), (-1, 1, 4.0), (0, 1, 8.0), (1, 1, 4.0), (2, 1, 2.0),
+        (-2, 2, 1.0), (-1, 2, 2.0), (0, 2, 4.0), (1, 2, 2.0), (2, 2, 1.0),
+    ],
+};
+
+const BURKES: DiffusionKernel = DiffusionKernel {
+    divisor: 32.0,
+    taps: &[
+                                                (1, 0, 8.0), (2, 0, 4.0),
+        (-2, 1, 2.0), (-1, 1, 4.0), (0, 1, 8.0), (1, 1, 4.0), (2, 1, 2.0),
+    ],
+};
 
-fn find_closest_color(color: Rgb<u8>, palette: &[Rgb<u8>]) -> Rgb<u8> {
-    let mut min_dist = f64::MAX;
-    let mut best_color = palette[0];
+const SIERRA: DiffusionKernel = DiffusionKernel {
+    divisor: 32.0,
+    taps: &[
+                                                (1, 0, 5.0), (2, 0, 3.0),
+        (-2, 1, 2.0), (-1, 1, 4.0), (0, 1, 5.0), (1, 1, 4.0), (2, 1, 2.0),
+                      (-1, 2, 2.0), (0, 2, 3.0), (1, 2, 2.0),
+    ],
+};
 
-    for p in palette {
-        let r_diff = color[0] as f64 - p[0] as f64;
-        let g_diff = color[1] as f64 - p[1] as f64;
-        let b_diff = color[2] as f64 - p[2] as f64;
-        let dist = r_diff * r_diff + g_diff * g_diff + b_diff * b_diff;
+const TWO_ROW_SIERRA: DiffusionKernel = DiffusionKernel {
+    divisor: 16.0,
+    taps: &[
+                                                (1, 0, 4.0), (2, 0, 3.0),
+        (-2, 1, 1.0), (-1, 1, 2.0), (0, 1, 3.0), (1, 1, 2.0), (2, 1, 1.0),
+    ],
+};
+
+const SIERRA_LITE: DiffusionKernel = DiffusionKernel {
+    divisor: 4.0,
+    taps: &[
+                      (1, 0, 2.0),
+        (-1, 1, 1.0), (0, 1, 1.0),
+    ],
+};
+
+/// Dithering settings for `apply_dithering`. Field names match the frontend's
+/// `ProcessingParams`, which is why they carry a `dither` prefix.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DitheringConfig {
+    pub dither_algorithm: DitheringAlgorithm,
+    pub dither_strength: f32, // 0.0 to 1.0
+    // Alternate scan direction every row (boustrophedon), mirroring the
+    // diffusion kernel on right-to-left rows. Ignored by non-diffusion modes.
+    pub serpentine: bool,
+    // Ordered dithering: side of the generated Bayer matrix (power of two)...
+    pub bayer_size: u32,
+    // ...unless a custom threshold map is given
+    pub threshold_map: Option<ThresholdMap>,
+    // Video: max per-channel change (0-255) for which a pixel keeps its
+    // previous frame's decision. 0 dithers every frame from scratch.
+    pub temporal_threshold: f32,
+    // Distance used to pick the closest palette entry
+    pub color_metric: ColorMetric,
+}
+
+impl Default for DitheringConfig {
+    fn default() -> Self {
+        Self {
+            dither_algorithm: DitheringAlgorithm::None,
+            dither_strength: 0.5,
+            serpentine: false,
+            bayer_size: 4,
+            threshold_map: None,
+            temporal_threshold: 0.0,
+            color_metric: ColorMetric::Rgb,
+        }
+    }
+}
 
-        if dist < min_dist {
-            min_dist = dist;
-            best_color = *p;
+impl DitheringAlgorithm {
+    /// The kernel for error-diffusion algorithms, None for the others.
+    pub fn diffusion_kernel(self) -> Option<&'static DiffusionKernel> {
+        match self {
+            DitheringAlgorithm::None | DitheringAlgorithm::Ordered | DitheringAlgorithm::BlueNoise => None,
+            DitheringAlgorithm::FloydSteinberg => Some(&FLOYD_STEINBERG),
+            DitheringAlgorithm::Atkinson => Some(&ATKINSON),
+            DitheringAlgorithm::JarvisJudiceNinke => Some(&JARVIS_JUDICE_NINKE),
+            DitheringAlgorithm::Stucki => Some(&STUCKI),
+            DitheringAlgorithm::Burkes => Some(&BURKES),
+            DitheringAlgorithm::Sierra => Some(&SIERRA),
+            DitheringAlgorithm::TwoRowSierra => Some(&TWO_ROW_SIERRA),
+            DitheringAlgorithm::SierraLite => Some(&SIERRA_LITE),
         }
     }
-    best_color
+}
+
+/// Dither decisions carried between consecutive video frames, see
+/// `apply_dithering_temporal`.
+#[derive(Default)]
+pub struct TemporalState {
+    // Source colour each output decision was made for
+    reference: Option<RgbImage>,
+    output: Option<RgbImage>,
+    target: Option<ColorTarget>,
+}
+
+impl TemporalState {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// The previous frame's decisions, reused for pixels whose source colour moved
+// by at most `threshold` on every channel since the decision was made. When
+// the colour target changed since (e.g. an automatic palette rebuilt for each
+// frame), a kept colour is swapped for its nearest colour in the new target.
+struct PreviousFrame<'a> {
+    reference: &'a RgbImage,
+    output: &'a RgbImage,
+    threshold: f32,
+    remap: bool,
+}
+
+impl PreviousFrame<'_> {
+    fn is_unchanged(&self, x: u32, y: u32, current: Rgb<u8>) -> bool {
+        let reference = self.reference.get_pixel(x, y);
+        (0..3).all(|c| (reference[c] as f32 - current[c] as f32).abs() <= self.threshold)
+    }
+
+    fn decision<N>(&self, x: u32, y: u32, current: Rgb<u8>, nearest: &N) -> Option<Rgb<u8>>
+    where
+        N: Fn(u32, u32, Rgb<u8>) -> Rgb<u8>,
+    {
+        self.is_unchanged(x, y, current).then(|| {
+            let kept = *self.output.get_pixel(x, y);
+            if self.remap { nearest(x, y, kept) } else { kept }
+        })
+    }
 }
 
 pub fn apply_dithering(
     img: &RgbImage,
-    palette: &[Rgb<u8>],
-    algo: DitheringAlgorithm,
-    strength: f32
+    target: &ColorTarget,
+    config: &DitheringConfig
+) -> RgbImage {
+    let matcher = PaletteMatcher::for_target(target, config.color_metric);
+    dither_frame(img, &|_, _, color| matcher.nearest(color), config, None)
+}
+
+/// Like `apply_dithering`, but each pixel is quantized by `nearest(x, y, colour)`,
+/// e.g. to the few colours its attribute cell allows.
+pub fn apply_dithering_with<N>(img: &RgbImage, config: &DitheringConfig, nearest: N) -> RgbImage
+where
+    N: Fn(u32, u32, Rgb<u8>) -> Rgb<u8> + Sync,
+{
+    dither_frame(img, &nearest, config, None)
+}
+
+/// Like `apply_dithering`, but pixels whose source colour stayed within
+/// `config.temporal_threshold` of the previous frame keep their previous
+/// colour, so static areas don't crawl or flicker between frames.
+/// Error diffusion carries on from the kept colours, so the pattern around
+/// changed areas stays seamless. If `target` differs from the previous
+/// frame's, kept colours are mapped to their nearest colour in it. A
+/// threshold of 0 disables this.
+pub fn apply_dithering_temporal(
+    img: &RgbImage,
+    target: &ColorTarget,
+    config: &DitheringConfig,
+    state: &mut TemporalState
 ) -> RgbImage {
-    let (width, height) = img.dimensions();
+    if config.temporal_threshold <= 0.0 {
+        state.reset();
+        return apply_dithering(img, target, config);
+    }
+
+    // A resized frame invalidates every old decision
+    let compatible = state.reference.as_ref().is_some_and(|r| r.dimensions() == img.dimensions());
+
+    let previous = match (&state.reference, &state.output) {
+        (Some(reference), Some(output)) if compatible => Some(PreviousFrame {
+            reference,
+            output,
+            threshold: config.temporal_threshold,
+            remap: state.target.as_ref() != Some(target),
+        }),
+        _ => None,
+    };
+
+    let matcher = PaletteMatcher::for_target(target, config.color_metric);
+    let output = dither_frame(img, &|_, _, color| matcher.nearest(color), config, previous.as_ref());
+
+    // Kept pixels keep their old reference, so slow drift still triggers a
+    // fresh decision once it adds up past the threshold
+    let mut reference = img.clone();
+    if let Some(previous) = &previous {
+        for_each_row(&mut reference, |y, row| {
+            for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                let current = Rgb([pixel[0], pixel[1], pixel[2]]);
+                if previous.is_unchanged(x as u32, y, current) {
+                    pixel.copy_from_slice(&previous.reference.get_pixel(x as u32, y).0);
+                }
+            }
+        });
+    }
+
+    state.reference = Some(reference);
+    state.output = Some(output.clone());
+    state.target = Some(target.clone());
+    output
+}
+
+// Runs `f(y, row)` over the rows of `img` in parallel, each row as packed RGB bytes
+fn for_each_row(img: &mut RgbImage, f: impl Fn(u32, &mut [u8]) + Sync) {
+    let row_len = img.width() as usize * 3;
+    if row_len == 0 {
+        return;
+    }
+    img.par_chunks_mut(row_len)
+        .enumerate()
+        .for_each(|(y, row)| f(y as u32, row));
+}
+
+fn dither_frame<N>(
+    img: &RgbImage,
+    nearest: &N,
+    config: &DitheringConfig,
+    previous: Option<&PreviousFrame>
+) -> RgbImage
+where
+    N: Fn(u32, u32, Rgb<u8>) -> Rgb<u8> + Sync,
+{
     let mut output = img.clone();
+    let algo = config.dither_algorithm;
+    let strength = config.dither_strength;
 
     match algo {
         DitheringAlgorithm::None => {
             // Simple nearest neighbor mapping
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = output.get_pixel_mut(x, y);
-                    *pixel = find_closest_color(*pixel, palette);
+            for_each_row(&mut output, |y, row| {
+                for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                    let old_color = Rgb([pixel[0], pixel[1], pixel[2]]);
+                    let new_color = previous.and_then(|p| p.decision(x as u32, y, old_color, nearest))
+                        .unwrap_or_else(|| nearest(x as u32, y, old_color));
+                    pixel.copy_from_slice(&new_color.0);
                 }
-            }
+            });
         },
-        DitheringAlgorithm::Ordered => {
-            // Ordered Dithering (Bayer)
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = output.get_pixel_mut(x, y);
-                    let old_color = *pixel;
-                    
-                    // Get threshold from Bayer matrix (normalized 0-255)
-                    let threshold = BAYER_MATRIX[((y % 4) * 4 + (x % 4)) as usize] as f32 / 16.0;
+        DitheringAlgorithm::Ordered | DitheringAlgorithm::BlueNoise => {
+            // Ordered Dithering (Bayer, custom threshold map or blue noise)
+            let bayer;
+            let map = match (algo, &config.threshold_map) {
+                (DitheringAlgorithm::BlueNoise, _) => blue_noise(),
+                (_, Some(map)) => map,
+                _ => {
+                    bayer = ThresholdMap::bayer(config.bayer_size);
+                    &bayer
+                }
+            };
+
+            for_each_row(&mut output, |y, row| {
+                for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                    let x = x as u32;
+                    let old_color = Rgb([pixel[0], pixel[1], pixel[2]]);
+
+                    if let Some(kept) = previous.and_then(|p| p.decision(x, y, old_color, nearest)) {
+                        pixel.copy_from_slice(&kept.0);
+                        continue;
+                    }
+
+                    // Get threshold from the map (normalized 0-1)
+                    let threshold = map.threshold(x, y);
                     let factor = (threshold - 0.5) * 255.0 * strength;
 
                     let r = (old_color[0] as f32 + factor).clamp(0.0, 255.0) as u8;
                     let g = (old_color[1] as f32 + factor).clamp(0.0, 255.0) as u8;
                     let b = (old_color[2] as f32 + factor).clamp(0.0, 255.0) as u8;
-                    
-                    *pixel = find_closest_color(Rgb([r, g, b]), palette);
+
+                    pixel.copy_from_slice(&nearest(x, y, Rgb([r, g, b])).0);
                 }
-            }
+            });
         },
-        DitheringAlgorithm::FloydSteinberg => {
-            // Floyd-Steinberg Error Diffusion
-            // We need to work with f32 errors, so we'll store them in a buffer
-            // Since error propogates right and down, we need to process sequentially
-            
-            // To implement this efficiently in Rust without unsafe, we can just iterate carefully
-            // However, modifying pixels in-place while iterating is tricky with RgbImage if we want to propagate error.
-            // We will use a float buffer for intermediate calculations.
-            
-            let mut error_buffer: Vec<f32> = vec![0.0; (width * height * 3) as usize];
-            
-            // Initialize error buffer with original image
-            for (i, pixel) in img.pixels().enumerate() {
-                error_buffer[i * 3] = pixel[0] as f32;
-                error_buffer[i * 3 + 1] = pixel[1] as f32;
-                error_buffer[i * 3 + 2] = pixel[2] as f32;
+        _ => {
+            // Error diffusion, driven by the algorithm's kernel table
+            if let Some(kernel) = algo.diffusion_kernel() {
+                let diffusion = Diffusion::new(img, nearest, kernel, strength, config.serpentine, previous);
+                output = diffusion.run();
             }
+        }
+    }
+
+    output
+}
+
+// Error diffusion, computed "pull" style: each pixel adds up the error its
+// kernel sources left for it, instead of each pixel pushing error forward.
+// Every colour and error is then written exactly once, by the pixel that owns
+// it, so rows can run concurrently as long as their sources are finished, and
+// the result doesn't depend on scheduling.
+//
+// Raster scans run as a wavefront over cells of one row by WAVEFRONT_BLOCK
+// columns: cell (row, block) only needs its left neighbour and the row above
+// up to the next block, so every cell on the anti-diagonal 2 * row + block
+// can run in parallel. Serpentine rows start where the row above ends, which
+// leaves nothing to overlap, so they run sequentially.
+struct Diffusion<'a, N> {
+    img: &'a RgbImage,
+    nearest: &'a N,
+    kernel: &'a DiffusionKernel,
+    // The kernel's taps in the order their sources are scanned (earlier rows
+    // first, then along the row), so error adds up in the same order as a
+    // sequential push would and the output is bit-identical to it
+    sources: Vec<(i32, i32, f32)>,
+    strength: f32,
+    serpentine: bool,
+    previous: Option<&'a PreviousFrame<'a>>,
+    // Chosen colour per pixel as 0xRRGGBB, and scaled quantization error per
+    // channel as f32 bits. Atomics only to share them between threads; rayon's
+    // join between waves orders each write before the reads that follow.
+    colors: Vec<AtomicU32>,
+    errors: Vec<AtomicU32>,
+}
 
+impl<'a, N> Diffusion<'a, N>
+where
+    N: Fn(u32, u32, Rgb<u8>) -> Rgb<u8> + Sync,
+{
+    fn new(
+        img: &'a RgbImage,
+        nearest: &'a N,
+        kernel: &'a DiffusionKernel,
+        strength: f32,
+        serpentine: bool,
+        previous: Option<&'a PreviousFrame<'a>>
+    ) -> Self {
+        let pixels = img.width() as usize * img.height() as usize;
+        let mut sources = kernel.taps.to_vec();
+        sources.sort_by_key(|&(dx, dy, _)| (std::cmp::Reverse(dy), std::cmp::Reverse(dx)));
+
+        Self {
+            img,
+            nearest,
+            kernel,
+            sources,
+            strength,
+            serpentine,
+            previous,
+            colors: (0..pixels).map(|_| AtomicU32::new(0)).collect(),
+            errors: (0..pixels * 3).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    fn run(self) -> RgbImage {
+        let (width, height) = self.img.dimensions();
+
+        // How far left of a pixel the row above may still push error into it
+        let reach = self.sources.iter()
+            .filter(|&&(_, dy, _)| dy > 0)
+            .map(|&(dx, dy, _)| ((-dx).max(0) as u32).div_ceil(dy as u32))
+            .max()
+            .unwrap_or(0);
+        let block = WAVEFRONT_BLOCK.max(reach);
+        let blocks = width.div_ceil(block);
+
+        if self.serpentine || blocks < 2 || height < 2 {
             for y in 0..height {
-                for x in 0..width {
-                    let idx = ((y * width + x) * 3) as usize;
-                    
-                    let old_r = error_buffer[idx];
-                    let old_g = error_buffer[idx + 1];
-                    let old_b = error_buffer[idx + 2];
-                    
-                    let old_color = Rgb([
-                        old_r.clamp(0.0, 255.0) as u8,
-                        old_g.clamp(0.0, 255.0) as u8,
-                        old_b.clamp(0.0, 255.0) as u8,
-                    ]);
-                    
-                    let new_color = find_closest_color(old_color, palette);
-                    output.put_pixel(x, y, new_color);
-                    
-                    let quant_error_r = (old_r - new_color[0] as f32) * strength;
-                    let quant_error_g = (old_g - new_color[1] as f32) * strength;
-                    let quant_error_b = (old_b - new_color[2] as f32) * strength;
-                    
-                    // Distribute error
-                    // Right: 7/16
-                    if x + 1 < width {
-                        let n_idx = ((y * width + (x + 1)) * 3) as usize;
-                        error_buffer[n_idx] += quant_error_r * 7.0 / 16.0;
-                        error_buffer[n_idx + 1] += quant_error_g * 7.0 / 16.0;
-                        error_buffer[n_idx + 2] += quant_error_b * 7.0 / 16.0;
-                    }
-                    
-                    // Bottom Left: 3/16
-                    if x > 0 && y + 1 < height {
-                        let n_idx = (((y + 1) * width + (x - 1)) * 3) as usize;
-                        error_buffer[n_idx] += quant_error_r * 3.0 / 16.0;
-                        error_buffer[n_idx + 1] += quant_error_g * 3.0 / 16.0;
-                        error_buffer[n_idx + 2] += quant_error_b * 3.0 / 16.0;
-                    }
-                    
-                    // Bottom: 5/16
-                    if y + 1 < height {
-                        let n_idx = (((y + 1) * width + x) * 3) as usize;
-                        error_buffer[n_idx] += quant_error_r * 5.0 / 16.0;
-                        error_buffer[n_idx + 1] += quant_error_g * 5.0 / 16.0;
-                        error_buffer[n_idx + 2] += quant_error_b * 5.0 / 16.0;
+                self.diffuse_span(y, 0..width);
+            }
+        } else {
+            for wave in 0..2 * (height - 1) + blocks {
+                let first_row = (wave + 1).saturating_sub(blocks).div_ceil(2);
+                let last_row = (wave / 2).min(height - 1);
+                (first_row..=last_row).into_par_iter().for_each(|y| {
+                    let start = (wave - 2 * y) * block;
+                    self.diffuse_span(y, start..(start + block).min(width));
+                });
+            }
+        }
+
+        let mut output = RgbImage::new(width, height);
+        for (pixel, color) in output.pixels_mut().zip(&self.colors) {
+            let [_, r, g, b] = color.load(Ordering::Relaxed).to_be_bytes();
+            *pixel = Rgb([r, g, b]);
+        }
+        output
+    }
+
+    // Scan direction of row `y`: 1 = left to right, -1 = right to left
+    fn direction(&self, y: u32) -> i64 {
+        if self.serpentine && y % 2 == 1 { -1 } else { 1 }
+    }
+
+    // Processes `columns` of row `y` in the row's scan order. On right-to-left
+    // rows the kernel is mirrored horizontally.
+    fn diffuse_span(&self, y: u32, columns: std::ops::Range<u32>) {
+        let width = self.img.width();
+        let reversed = self.direction(y) < 0;
+
+        for i in columns {
+            let x = if reversed { width - 1 - i } else { i };
+            let source = self.img.get_pixel(x, y);
+            let mut value = [source[0] as f32, source[1] as f32, source[2] as f32];
+
+            for &(dx, dy, weight) in &self.sources {
+                // The pixel whose error reaches (x, y) through this tap
+                let sy = y as i64 - dy as i64;
+                if sy < 0 {
+                    continue;
+                }
+                let sx = x as i64 - dx as i64 * self.direction(sy as u32);
+                if sx < 0 || sx >= width as i64 {
+                    continue;
+                }
+
+                let s_idx = (sy as usize * width as usize + sx as usize) * 3;
+                for (c, v) in value.iter_mut().enumerate() {
+                    *v += f32::from_bits(self.errors[s_idx + c].load(Ordering::Relaxed)) * weight;
+                }
+            }
+
+            let old_color = Rgb(value.map(|v| v.clamp(0.0, 255.0) as u8));
+
+            // Temporal reuse compares the untouched source pixel, not the
+            // error-adjusted one, which changes whenever a neighbour does
+            let new_color = self.previous.and_then(|p| p.decision(x, y, *source, self.nearest))
+                .unwrap_or_else(|| (self.nearest)(x, y, old_color));
+
+            let idx = y as usize * width as usize + x as usize;
+            let [r, g, b] = new_color.0;
+            self.colors[idx].store(u32::from_be_bytes([0, r, g, b]), Ordering::Relaxed);
+            for (c, v) in value.iter().enumerate() {
+                let error = (v - new_color[c] as f32) * self.strength / self.kernel.divisor;
+                self.errors[idx * 3 + c].store(error.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::BitDepth;
+    use crate::test_util;
+
+    const DIFFUSION: [DitheringAlgorithm; 8] = [
+        DitheringAlgorithm::FloydSteinberg,
+        DitheringAlgorithm::Atkinson,
+        DitheringAlgorithm::JarvisJudiceNinke,
+        DitheringAlgorithm::Stucki,
+        DitheringAlgorithm::Burkes,
+        DitheringAlgorithm::Sierra,
+        DitheringAlgorithm::TwoRowSierra,
+        DitheringAlgorithm::SierraLite,
+    ];
+
+    // Odd-sized and several wavefront blocks wide
+    fn gradient() -> RgbImage {
+        test_util::gradient(3 * WAVEFRONT_BLOCK + 7, 41)
+    }
+
+    // Textbook push-style diffusion, one pixel at a time
+    fn sequential(img: &RgbImage, target: &ColorTarget, kernel: &DiffusionKernel, strength: f32, serpentine: bool) -> RgbImage {
+        let matcher = PaletteMatcher::for_target(target, ColorMetric::Rgb);
+        let (width, height) = img.dimensions();
+        let mut values: Vec<[f32; 3]> = img.pixels().map(|p| p.0.map(|c| c as f32)).collect();
+        let mut output = RgbImage::new(width, height);
+
+        for y in 0..height {
+            let reversed = serpentine && y % 2 == 1;
+            let direction = if reversed { -1 } else { 1 };
+            for i in 0..width {
+                let x = if reversed { width - 1 - i } else { i };
+                let value = values[(y * width + x) as usize];
+                let new_color = matcher.nearest(Rgb(value.map(|v| v.clamp(0.0, 255.0) as u8)));
+                output.put_pixel(x, y, new_color);
+
+                let error: [f32; 3] = std::array::from_fn(|c| (value[c] - new_color[c] as f32) * strength / kernel.divisor);
+                for &(dx, dy, weight) in kernel.taps {
+                    let (tx, ty) = (x as i64 + dx as i64 * direction, y as i64 + dy as i64);
+                    if tx < 0 || tx >= width as i64 || ty >= height as i64 {
+                        continue;
                     }
-                    
-                    // Bottom Right: 1/16
-                    if x + 1 < width && y + 1 < height {
-                        let n_idx = (((y + 1) * width + (x + 1)) * 3) as usize;
-                        error_buffer[n_idx] += quant_error_r * 1.0 / 16.0;
-                        error_buffer[n_idx + 1] += quant_error_g * 1.0 / 16.0;
-                        error_buffer[n_idx + 2] += quant_error_b * 1.0 / 16.0;
+                    let target = &mut values[(ty * width as i64 + tx) as usize];
+                    for c in 0..3 {
+                        target[c] += error[c] * weight;
                     }
                 }
             }
         }
+        output
     }
 
-    output
+    #[test]
+    fn wavefront_matches_sequential_scan() {
+        let img = gradient();
+        let target = ColorTarget::Grid(BitDepth::uniform(1));
+        for algorithm in DIFFUSION {
+            for serpentine in [false, true] {
+                let config = DitheringConfig {
+                    dither_algorithm: algorithm,
+                    dither_strength: 0.8,
+                    serpentine,
+                    ..Default::default()
+                };
+                let kernel = algorithm.diffusion_kernel().unwrap();
+                assert!(
+                    apply_dithering(&img, &target, &config) == sequential(&img, &target, kernel, 0.8, serpentine),
+                    "{:?}, serpentine {}", algorithm, serpentine
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_diffuse_the_whole_error() {
+        for algorithm in DIFFUSION {
+            let kernel = algorithm.diffusion_kernel().unwrap();
+            let total: f32 = kernel.taps.iter().map(|&(_, _, weight)| weight).sum();
+            // Atkinson drops a quarter of the error on purpose
+            let expected = if algorithm == DitheringAlgorithm::Atkinson { 0.75 } else { 1.0 };
+            assert_eq!(total / kernel.divisor, expected, "{:?}", algorithm);
+            // Every tap lies ahead of the pixel in scan order
+            assert!(kernel.taps.iter().all(|&(dx, dy, _)| dy > 0 || (dy == 0 && dx > 0)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn temporal_state_survives_palette_changes() {
+        let img = gradient();
+        let config = DitheringConfig {
+            dither_algorithm: DitheringAlgorithm::Ordered,
+            temporal_threshold: 8.0,
+            ..Default::default()
+        };
+        let first = ColorTarget::Palette(vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])]);
+        // Same colours, slightly off, as a rebuilt automatic palette would be
+        let second = ColorTarget::Palette(vec![Rgb([250, 250, 250]), Rgb([3, 3, 3])]);
+
+        let mut state = TemporalState::default();
+        let before = apply_dithering_temporal(&img, &first, &config, &mut state);
+        let shifted = RgbImage::from_fn(img.width(), img.height(), |x, y| {
+            Rgb(img.get_pixel(x, y).0.map(|c| c.saturating_add(4)))
+        });
+        let after = apply_dithering_temporal(&shifted, &second, &config, &mut state);
+
+        let matcher = PaletteMatcher::new(&[Rgb([250, 250, 250]), Rgb([3, 3, 3])], ColorMetric::Rgb);
+        for (b, a) in before.pixels().zip(after.pixels()) {
+            assert_eq!(*a, matcher.nearest(*b));
+        }
+    }
 }
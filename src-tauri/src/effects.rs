@@ -1,107 +1,692 @@
+use image::{Rgb, RgbImage, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use image::{Rgb, RgbImage};
+use crate::color::{linear_to_srgb, rgb_to_yiq, rgb_to_yuv, srgb_to_linear, yiq_to_rgb, yuv_to_rgb};
 
-// ... (skipping context match, just replace whole file or specific chunks?)
-// I'll replace the import first
+// Blur radii as a fraction of the image height: bloom hugs bright areas,
+// halation spreads through the whole glass
+const BLOOM_RADIUS: f32 = 1.0 / 120.0;
+const HALATION_RADIUS: f32 = 1.0 / 25.0;
 
+// Box blur passes; three approximate a gaussian
+const BLUR_PASSES: usize = 3;
 
-#[derive(Clone, Copy, Debug)]
+// Subcarrier cycles across the visible part of a line. The composite signal is
+// sampled 4 times per cycle, so carrier samples are exactly 1, 0, -1, 0.
+const NTSC_CYCLES: usize = 188;
+const PAL_CYCLES: usize = 230;
+const SAMPLES_PER_CYCLE: usize = 4;
+
+// Luma filter with zeros at the subcarrier and twice its frequency
+const NOTCH: [f32; 5] = [0.125, 0.25, 0.25, 0.25, 0.125];
+// Cheap luma filter that only halves the subcarrier
+const TRAP: [f32; 3] = [0.25, 0.5, 0.25];
+// Radius in samples of the blur luma ringing overshoots
+const RINGING_RADIUS: usize = 3;
+
+/// Phosphor layout of the emulated tube, drawn with a 3-pixel RGB pitch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaskType {
+    #[default]
+    None,
+    ApertureGrille, // continuous vertical stripes (Trinitron)
+    SlotMask,       // stripes broken into staggered slots
+    ShadowMask,     // triads of dots, offset on alternate rows
+}
+
+/// How the curvature warp samples the flat image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResampleFilter {
+    #[default]
+    Nearest,  // hard pixel edges, stair-stepped where the warp bends them
+    Bilinear, // 2x2 neighbourhood
+    Bicubic,  // 4x4 Catmull-Rom, sharper than bilinear
+}
+
+/// Analog video signal the low-resolution frame goes through on its way to
+/// the tube.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum CompositePreset {
+    #[default]
+    None,
+    NTSC, // YIQ on a 3.58 MHz subcarrier: dot crawl and rainbows on fine detail
+    PAL,  // YUV with V alternating per line, chroma averaged over line pairs
+    RF,   // NTSC through an aerial socket: softer, more crosstalk, snow
+}
+
+// How a CompositePreset encodes and decodes each line
+struct CompositeSignal {
+    cycles: usize,      // subcarrier cycles per line
+    line_phase: usize,  // quarter cycles the subcarrier advances every line
+    frame_phase: usize, // and every frame, which makes dot crawl move
+    pal: bool,
+    crosstalk: f32,     // 0.0 to 1.0, subcarrier left in the luma
+    luma_blur: usize,   // extra luma lowpass radius, in samples
+    chroma_blur: usize, // chroma lowpass radius, in samples
+    ringing: f32,       // overshoot either side of sharp luma edges
+    noise: f32,         // snow amplitude, relative to full scale
+}
+
+impl CompositePreset {
+    fn signal(self) -> Option<CompositeSignal> {
+        let ntsc = CompositeSignal {
+            cycles: NTSC_CYCLES,
+            line_phase: 2, // 227.5 cycles per line
+            frame_phase: 2,
+            pal: false,
+            crosstalk: 0.2,
+            luma_blur: 0,
+            chroma_blur: 4,
+            ringing: 0.3,
+            noise: 0.0,
+        };
+        match self {
+            CompositePreset::None => None,
+            CompositePreset::NTSC => Some(ntsc),
+            CompositePreset::PAL => Some(CompositeSignal {
+                cycles: PAL_CYCLES,
+                line_phase: 3, // 283.75 cycles per line
+                frame_phase: 1,
+                pal: true,
+                crosstalk: 0.1,
+                chroma_blur: 3,
+                ringing: 0.2,
+                ..ntsc
+            }),
+            CompositePreset::RF => Some(CompositeSignal {
+                crosstalk: 0.5,
+                luma_blur: 2,
+                chroma_blur: 6,
+                ringing: 0.5,
+                noise: 0.15,
+                ..ntsc
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct CRTEffectsConfig {
+    pub composite: CompositePreset, // applied to the low-res frame, see apply_composite
     pub scanline_intensity: f32, // 0.0 to 1.0
+    pub scanline_thickness: f32, // 0.0 to 1.0, share of each line the beam lights
+    pub scanline_softness: f32,  // 0.0 to 1.0, 0.0 = hard beam edges
+    pub scanline_count: u32,     // beams per low-res pixel row
     pub curvature_strength: f32, // 0.0 to 1.0 (0.0 = flat, 1.0 = heavy curve)
+    pub curvature_filter: ResampleFilter,
+    pub corner_radius: f32,      // 0.0 to 1.0, rounding of the screen corners relative to the shorter side
     pub vignette_strength: f32,  // 0.0 to 1.0
+    pub mask_type: MaskType,
+    pub mask_intensity: f32,     // 0.0 to 1.0, how dark the unlit phosphors are
+    pub beam_width: f32,         // 0.0 to 1.0, how far bright pixels spread into the scanline gaps
+    pub bloom_intensity: f32,    // 0.0 to 1.0, glow around bright areas
+    pub halation_intensity: f32, // 0.0 to 1.0, wide haze of light scattered in the glass
+    pub gamma_correct: bool,     // blend in linear light instead of on sRGB values
 }
 
 impl Default for CRTEffectsConfig {
     fn default() -> Self {
         Self {
+            composite: CompositePreset::None,
             scanline_intensity: 0.0,
+            scanline_thickness: 0.5,
+            scanline_softness: 0.5,
+            scanline_count: 1,
             curvature_strength: 0.0,
+            curvature_filter: ResampleFilter::Nearest,
+            corner_radius: 0.0,
             vignette_strength: 0.0,
+            mask_type: MaskType::None,
+            mask_intensity: 0.5,
+            beam_width: 0.0,
+            bloom_intensity: 0.0,
+            halation_intensity: 0.0,
+            gamma_correct: false,
         }
     }
 }
 
-pub fn apply_crt_effects(img: &RgbImage, config: CRTEffectsConfig) -> RgbImage {
+impl CRTEffectsConfig {
+    fn has_mask(&self) -> bool {
+        self.mask_type != MaskType::None && self.mask_intensity > 0.0
+    }
+
+    fn is_active(&self) -> bool {
+        self.scanline_intensity > 0.0
+            || self.curvature_strength > 0.0
+            || self.corner_radius > 0.0
+            || self.vignette_strength > 0.0
+            || self.has_mask()
+            || self.bloom_intensity > 0.0
+            || self.halation_intensity > 0.0
+    }
+}
+
+// Light of one pixel, 0.0 to 255.0 per channel: sRGB values, or linear light
+// scaled to the same range when blending gamma-correctly
+type Light = [f32; 3];
+
+/// Applies the CRT model to `img`, an upscale of a `source_height`-row frame;
+/// scanlines follow those rows.
+pub fn apply_crt_effects(img: &RgbImage, source_height: u32, config: CRTEffectsConfig) -> RgbImage {
     let (width, height) = img.dimensions();
-    
+
     // If no effects, return original
-    if config.scanline_intensity <= 0.0 && config.curvature_strength <= 0.0 && config.vignette_strength <= 0.0 {
+    if !config.is_active() {
         return img.clone();
     }
 
-    let mut output = RgbImage::new(width, height);
+    if width == 0 || height == 0 {
+        return RgbImage::new(width, height);
+    }
 
-    for y in 0..height {
-        for x in 0..width {
-            // Curvature Distortion
-            let (src_x, src_y) = if config.curvature_strength > 0.0 {
-                // Normalize coordinates to -1.0 to 1.0
-                let nx = (x as f32 / width as f32) * 2.0 - 1.0;
-                let ny = (y as f32 / height as f32) * 2.0 - 1.0;
-                
-                // Calculate distance from center
-                let dist = nx * nx + ny * ny;
-                let distortion = 1.0 + dist * (config.curvature_strength * 0.2); // Scale strength
-                
-                let dn_x = nx * distortion;
-                let dn_y = ny * distortion;
-                
-                // Map back to pixel coordinates
-                let map_x = (dn_x + 1.0) / 2.0 * width as f32;
-                let map_y = (dn_y + 1.0) / 2.0 * height as f32;
-                
-                (map_x, map_y)
-            } else {
-                (x as f32, y as f32)
-            };
+    // Output rows per scanline
+    let line_pitch = height as f32 / (source_height.max(1) * config.scanline_count.max(1)) as f32;
+    let corner_radius = config.corner_radius.clamp(0.0, 1.0) * width.min(height) as f32 / 2.0;
+
+    let decode = |pixel: &Rgb<u8>| -> Light {
+        if config.gamma_correct {
+            srgb_to_linear(*pixel).map(|c| c * 255.0)
+        } else {
+            [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+        }
+    };
+
+    // Geometry, beam and phosphors: every output pixel only reads the input,
+    // so rows render in parallel
+    let mut light: Vec<Light> = vec![[0.0; 3]; (width * height) as usize];
+    light.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+        let y = y as u32;
+        for (x, out) in row.iter_mut().enumerate() {
+            let x = x as u32;
+            // Where the centre of this pixel lands on the flat image
+            let (src_x, src_y) = curve(x as f32 + 0.5, y as f32 + 0.5, width, height, config.curvature_strength);
 
-            // Check bounds
-            if src_x < 0.0 || src_x >= width as f32 || src_y < 0.0 || src_y >= height as f32 {
-                output.put_pixel(x, y, Rgb([0, 0, 0])); // Black outside bounds
-                continue;
+            // Share of the pixel on the screen, anti-aliasing its edges and corners
+            let coverage = screen_coverage(src_x, src_y, width, height, corner_radius);
+            if coverage <= 0.0 {
+                continue; // Black outside the screen
             }
 
-            // Sample original pixel (Nearest Neighbor for retro look)
-            let pixel = img.get_pixel(src_x as u32, src_y as u32);
-            let mut r = pixel[0] as f32;
-            let mut g = pixel[1] as f32;
-            let mut b = pixel[2] as f32;
+            let mut color = sample(img, src_x, src_y, config.curvature_filter, &decode);
+            if coverage < 1.0 {
+                color = color.map(|c| c * coverage);
+            }
 
-            // Apply Scanlines
+            // Apply Scanlines, following the curved screen
             if config.scanline_intensity > 0.0 {
-                if y % 2 == 0 {
-                    let factor = 1.0 - config.scanline_intensity;
-                    r *= factor;
-                    g *= factor;
-                    b *= factor;
+                let luma = (0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]) / 255.0;
+                let beam = beam_profile(src_y / line_pitch, luma, &config);
+                let factor = 1.0 - config.scanline_intensity * (1.0 - beam);
+                color = color.map(|c| c * factor);
+            }
+
+            if config.has_mask() {
+                let mask = mask_weights(config.mask_type, x, y, config.mask_intensity);
+                for (c, m) in color.iter_mut().zip(mask) {
+                    *c *= m;
                 }
             }
-            
-            // Apply Vignette
-            if config.vignette_strength > 0.0 {
-                let nx = (x as f32 / width as f32) * 2.0 - 1.0;
-                let ny = (y as f32 / height as f32) * 2.0 - 1.0;
-                let dist = (nx * nx + ny * ny).sqrt();
-                
-                // Vignette falloff
-                let radius = 1.0 - config.vignette_strength * 0.5;
-                let softness = 0.4;
-                
-                let vig = 1.0 - ((dist - radius) / softness).clamp(0.0, 1.0);
-                
-                r *= vig;
-                g *= vig;
-                b *= vig;
+
+            *out = color;
+        }
+    });
+
+    // Light spilling through the glass, added on top
+    for (intensity, radius, bright_only) in [
+        (config.bloom_intensity, BLOOM_RADIUS, true),
+        (config.halation_intensity, HALATION_RADIUS, false),
+    ] {
+        if intensity <= 0.0 {
+            continue;
+        }
+        let source: Vec<Light> = if bright_only {
+            // Weighted by brightness, so only highlights glow
+            light.par_iter()
+                .map(|c| {
+                    let luma = (0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]) / 255.0;
+                    c.map(|v| v * luma * luma)
+                })
+                .collect()
+        } else {
+            light.clone()
+        };
+        let radius = ((height as f32 * radius).round() as usize).max(1);
+        let glow = blur(source, width as usize, height as usize, radius);
+        light.par_iter_mut().zip(glow).for_each(|(c, g)| {
+            for (v, g) in c.iter_mut().zip(g) {
+                *v += intensity * g;
+            }
+        });
+    }
+
+    let mut output = RgbImage::new(width, height);
+    output.par_chunks_mut(width as usize * 3)
+        .zip(light.par_chunks(width as usize))
+        .enumerate()
+        .for_each(|(y, (row, light))| {
+            for (x, (out, &color)) in row.chunks_exact_mut(3).zip(light).enumerate() {
+                let mut color = color;
+
+                // Apply Vignette
+                if config.vignette_strength > 0.0 {
+                    let vig = vignette(x as u32, y as u32, width, height, config.vignette_strength);
+                    color = color.map(|c| c * vig);
+                }
+
+                if config.gamma_correct {
+                    out.copy_from_slice(&linear_to_srgb(color.map(|c| c / 255.0)).0);
+                } else {
+                    out.copy_from_slice(&color.map(|c| c.clamp(0.0, 255.0) as u8));
+                }
             }
+        });
+
+    output
+}
+
+// Where the point (x, y) of a curved screen samples the flat image, both in
+// continuous pixel coordinates (pixel x spans x..x + 1)
+fn curve(x: f32, y: f32, width: u32, height: u32, strength: f32) -> (f32, f32) {
+    if strength <= 0.0 {
+        return (x, y);
+    }
+
+    // Normalize coordinates to -1.0 to 1.0
+    let nx = (x / width as f32) * 2.0 - 1.0;
+    let ny = (y / height as f32) * 2.0 - 1.0;
+
+    // Calculate distance from center
+    let dist = nx * nx + ny * ny;
+    let distortion = 1.0 + dist * (strength * 0.2); // Scale strength
+
+    let dn_x = nx * distortion;
+    let dn_y = ny * distortion;
+
+    // Map back to pixel coordinates
+    let map_x = (dn_x + 1.0) / 2.0 * width as f32;
+    let map_y = (dn_y + 1.0) / 2.0 * height as f32;
+
+    (map_x, map_y)
+}
+
+// How much of a one-pixel footprint at (x, y) lies inside the screen: a
+// `radius`-rounded rectangle over the whole image
+fn screen_coverage(x: f32, y: f32, width: u32, height: u32, radius: f32) -> f32 {
+    let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+    let qx = (x - half_width).abs() - (half_width - radius);
+    let qy = (y - half_height).abs() - (half_height - radius);
+    // Signed distance to the rounded rectangle, negative inside
+    let distance = qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius;
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+// The flat image at the continuous position (x, y), decoded into light
+fn sample(img: &RgbImage, x: f32, y: f32, filter: ResampleFilter, decode: &dyn Fn(&Rgb<u8>) -> Light) -> Light {
+    let (width, height) = img.dimensions();
+    let at = |px: i64, py: i64| {
+        decode(img.get_pixel(px.clamp(0, width as i64 - 1) as u32, py.clamp(0, height as i64 - 1) as u32))
+    };
+    // Positions relative to pixel centres
+    let (fx, fy) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (fx.floor() as i64, fy.floor() as i64);
+    let (tx, ty) = (fx - fx.floor(), fy - fy.floor());
 
-            output.put_pixel(x, y, Rgb([
-                r.clamp(0.0, 255.0) as u8,
-                g.clamp(0.0, 255.0) as u8,
-                b.clamp(0.0, 255.0) as u8
-            ]));
+    match filter {
+        ResampleFilter::Nearest => at(x.floor() as i64, y.floor() as i64),
+        ResampleFilter::Bilinear => {
+            let mut color = [0.0; 3];
+            for (dy, wy) in [(0, 1.0 - ty), (1, ty)] {
+                for (dx, wx) in [(0, 1.0 - tx), (1, tx)] {
+                    let c = at(x0 + dx, y0 + dy);
+                    for k in 0..3 {
+                        color[k] += c[k] * wx * wy;
+                    }
+                }
+            }
+            color
+        }
+        ResampleFilter::Bicubic => {
+            let (wx, wy) = (catmull_rom(tx), catmull_rom(ty));
+            let mut color = [0.0; 3];
+            for (dy, wy) in wy.into_iter().enumerate() {
+                for (dx, wx) in wx.into_iter().enumerate() {
+                    let c = at(x0 + dx as i64 - 1, y0 + dy as i64 - 1);
+                    for k in 0..3 {
+                        color[k] += c[k] * wx * wy;
+                    }
+                }
+            }
+            // Catmull-Rom overshoots at hard edges
+            color.map(|c| c.clamp(0.0, 255.0))
         }
     }
+}
 
+// Weights of the 4 taps around a sample `t` of the way from tap 1 to tap 2
+fn catmull_rom(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        (-t3 + 2.0 * t2 - t) / 2.0,
+        (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0,
+        (-3.0 * t3 + 4.0 * t2 + t) / 2.0,
+        (t3 - t2) / 2.0,
+    ]
+}
+
+/// Alpha-blends `bezel`, e.g. a TV frame with a transparent screen area, over
+/// `img`. Both must be the same size.
+pub fn apply_bezel(img: &mut RgbImage, bezel: &RgbaImage) {
+    img.par_chunks_mut(3).zip(bezel.par_chunks(4)).for_each(|(out, over)| {
+        let alpha = over[3] as u32;
+        for k in 0..3 {
+            out[k] = ((over[k] as u32 * alpha + out[k] as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    });
+}
+
+/// Encodes `img`, a low-resolution frame, to a composite signal and decodes it
+/// back, the way a console's video output reached the TV. `frame` is the
+/// frame's index in the video: the subcarrier phase moves from frame to frame,
+/// so dot crawl animates.
+pub fn apply_composite(img: &RgbImage, preset: CompositePreset, frame: u64) -> RgbImage {
+    let (width, height) = img.dimensions();
+    let Some(signal) = preset.signal() else {
+        return img.clone();
+    };
+    if width == 0 || height == 0 {
+        return img.clone();
+    }
+
+    let mut lines: Vec<Vec<[f32; 3]>> = (0..height).into_par_iter()
+        .map(|y| signal.line(img, y, frame))
+        .collect();
+
+    if signal.pal {
+        // The delay line averages each line's chroma with the line above,
+        // cancelling phase errors at the cost of vertical colour resolution
+        for y in (1..lines.len()).rev() {
+            let (above, below) = lines.split_at_mut(y);
+            for (c, a) in below[0].iter_mut().zip(&above[y - 1]) {
+                c[1] = (c[1] + a[1]) / 2.0;
+                c[2] = (c[2] + a[2]) / 2.0;
+            }
+        }
+    }
+
+    let to_rgb = if signal.pal { yuv_to_rgb } else { yiq_to_rgb };
+    let mut output = RgbImage::new(width, height);
+    output.par_chunks_mut(width as usize * 3).zip(lines).for_each(|(row, line)| {
+        for (out, color) in row.chunks_exact_mut(3).zip(line) {
+            out.copy_from_slice(&to_rgb(color).map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8));
+        }
+    });
     output
 }
+
+impl CompositeSignal {
+    // Row `y` of `img` through the encoder and decoder: luma and the two
+    // chroma components of every pixel
+    fn line(&self, img: &RgbImage, y: u32, frame: u64) -> Vec<[f32; 3]> {
+        let width = img.width() as usize;
+        let samples = self.cycles * SAMPLES_PER_CYCLE;
+        let from_rgb = if self.pal { rgb_to_yuv } else { rgb_to_yiq };
+        let pixels: Vec<[f32; 3]> = (0..width as u32).map(|x| from_rgb(*img.get_pixel(x, y))).collect();
+
+        let v_sign = if self.pal && y % 2 == 1 { -1.0 } else { 1.0 };
+        let phase = y as usize * self.line_phase + (frame % 4) as usize * self.frame_phase;
+        let carrier = |n: usize| {
+            let p = (n + phase) % 4;
+            ([1.0, 0.0, -1.0, 0.0][p], [0.0, 1.0, 0.0, -1.0][p] * v_sign)
+        };
+
+        // Pixels are held for their whole width, like a console's DAC
+        let composite: Vec<f32> = (0..samples)
+            .map(|n| {
+                let [luma, a, b] = pixels[n * width / samples];
+                let (cos, sin) = carrier(n);
+                let mut value = luma + a * cos + b * sin;
+                if self.noise > 0.0 {
+                    value += self.noise * noise(n as u32, y, frame);
+                }
+                value
+            })
+            .collect();
+
+        // Luma: the notch removes the subcarrier along with fine detail, the
+        // trap leaves half of it behind as dot crawl
+        let notched = convolve(&composite, &NOTCH);
+        let trapped = convolve(&composite, &TRAP);
+        let luma: Vec<f32> = notched.iter().zip(&trapped)
+            .map(|(n, t)| n + self.crosstalk * (t - n))
+            .collect();
+        let mut luma = lowpass(&luma, self.luma_blur);
+        if self.ringing > 0.0 {
+            let soft = lowpass(&luma, RINGING_RADIUS);
+            for (l, s) in luma.iter_mut().zip(soft) {
+                *l += self.ringing * (*l - s);
+            }
+        }
+
+        // Chroma: everything the trap rejects, fine luma detail included,
+        // which demodulates as rainbows. The trap passes half the subcarrier
+        // and demodulation another half, hence the gain of 4.
+        let (mut a, mut b) = (Vec::with_capacity(samples), Vec::with_capacity(samples));
+        for (n, (value, t)) in composite.iter().zip(&trapped).enumerate() {
+            let chroma = (value - t) * 4.0;
+            let (cos, sin) = carrier(n);
+            a.push(chroma * cos);
+            b.push(chroma * sin);
+        }
+        let a = lowpass(&convolve(&a, &NOTCH), self.chroma_blur);
+        let b = lowpass(&convolve(&b, &NOTCH), self.chroma_blur);
+
+        // Back to pixels, averaging the samples each one covers
+        (0..width)
+            .map(|x| {
+                let start = x * samples / width;
+                let end = ((x + 1) * samples / width).max(start + 1);
+                let span = (end - start) as f32;
+                [
+                    luma[start..end].iter().sum::<f32>() / span,
+                    a[start..end].iter().sum::<f32>() / span,
+                    b[start..end].iter().sum::<f32>() / span,
+                ]
+            })
+            .collect()
+    }
+}
+
+// `signal` filtered by `kernel`, centred, edges extended
+fn convolve(signal: &[f32], kernel: &[f32]) -> Vec<f32> {
+    let half = kernel.len() as isize / 2;
+    let last = signal.len() as isize - 1;
+    (0..signal.len() as isize)
+        .map(|n| {
+            kernel.iter().enumerate()
+                .map(|(k, w)| w * signal[(n + k as isize - half).clamp(0, last) as usize])
+                .sum()
+        })
+        .collect()
+}
+
+// Mean over a (2 * radius + 1)-sample window
+fn lowpass(signal: &[f32], radius: usize) -> Vec<f32> {
+    if radius == 0 {
+        return signal.to_vec();
+    }
+    convolve(signal, &vec![1.0 / (2 * radius + 1) as f32; 2 * radius + 1])
+}
+
+/// Uniform noise in -1.0..1.0, a pure function of its inputs so rows rendered
+/// in parallel and repeated renders agree.
+pub fn noise(x: u32, y: u32, frame: u64) -> f32 {
+    let mut h = (x as u64 | (y as u64) << 32) ^ frame.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    // splitmix64 finalizer
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+// Brightness of the beam at `line`, a position in scanlines (line n spans
+// n..n + 1 with the beam centred on it). Bright pixels widen the beam.
+fn beam_profile(line: f32, luma: f32, config: &CRTEffectsConfig) -> f32 {
+    let distance = (line.fract() - 0.5).abs();
+    let half = (config.scanline_thickness * (1.0 + config.beam_width * luma) / 2.0).min(0.5);
+    let soft = config.scanline_softness.clamp(0.0, 1.0) * 0.5;
+    if soft <= 0.0 {
+        return if distance <= half { 1.0 } else { 0.0 };
+    }
+    // Smoothstep across the edge
+    let t = ((distance - (half - soft / 2.0)) / soft).clamp(0.0, 1.0);
+    1.0 - t * t * (3.0 - 2.0 * t)
+}
+
+fn vignette(x: u32, y: u32, width: u32, height: u32, strength: f32) -> f32 {
+    let nx = (x as f32 / width as f32) * 2.0 - 1.0;
+    let ny = (y as f32 / height as f32) * 2.0 - 1.0;
+    let dist = (nx * nx + ny * ny).sqrt();
+
+    // Vignette falloff
+    let radius = 1.0 - strength * 0.5;
+    let softness = 0.4;
+
+    1.0 - ((dist - radius) / softness).clamp(0.0, 1.0)
+}
+
+// How much of each channel the phosphors at (x, y) let through
+fn mask_weights(mask: MaskType, x: u32, y: u32, intensity: f32) -> [f32; 3] {
+    let dark = 1.0 - intensity;
+    let lit = match mask {
+        MaskType::None => return [1.0; 3],
+        MaskType::ApertureGrille => x % 3,
+        MaskType::SlotMask => {
+            // Each slot is 3 rows tall with a 1-row gap, neighbouring columns
+            // of triads offset by half a slot
+            let triad = x / 3;
+            if (y + (triad % 2) * 2) % 4 == 3 {
+                return [dark; 3];
+            }
+            x % 3
+        }
+        // Alternate rows shift the triads sideways, giving the delta pattern
+        MaskType::ShadowMask => (x + (y % 2) * 2) % 3,
+    };
+    let mut weights = [dark; 3];
+    weights[lit as usize] = 1.0;
+    weights
+}
+
+// Approximately gaussian blur: repeated box blurs along rows, then along
+// columns by way of a transpose
+fn blur(light: Vec<Light>, width: usize, height: usize, radius: usize) -> Vec<Light> {
+    let mut light = light;
+    for _ in 0..BLUR_PASSES {
+        light = box_blur_rows(&light, width, radius);
+    }
+    let mut light = transpose(&light, width, height);
+    for _ in 0..BLUR_PASSES {
+        light = box_blur_rows(&light, height, radius);
+    }
+    transpose(&light, height, width)
+}
+
+// Mean over a (2 * radius + 1)-pixel window along each row, edges extended
+fn box_blur_rows(light: &[Light], width: usize, radius: usize) -> Vec<Light> {
+    let mut output = vec![[0.0; 3]; light.len()];
+    let window = (2 * radius + 1) as f32;
+    output.par_chunks_mut(width).zip(light.par_chunks(width)).for_each(|(out, row)| {
+        let at = |i: isize| row[i.clamp(0, width as isize - 1) as usize];
+        let mut sum = [0.0f32; 3];
+        for i in -(radius as isize)..=radius as isize {
+            let c = at(i);
+            for k in 0..3 {
+                sum[k] += c[k];
+            }
+        }
+        for (x, out) in out.iter_mut().enumerate() {
+            *out = sum.map(|s| s / window);
+            let (leaving, entering) = (at(x as isize - radius as isize), at(x as isize + radius as isize + 1));
+            for k in 0..3 {
+                sum[k] += entering[k] - leaving[k];
+            }
+        }
+    });
+    output
+}
+
+fn transpose(light: &[Light], width: usize, height: usize) -> Vec<Light> {
+    let mut output = vec![[0.0; 3]; light.len()];
+    output.par_chunks_mut(height).enumerate().for_each(|(x, column)| {
+        for (y, out) in column.iter_mut().enumerate() {
+            *out = light[y * width + x];
+        }
+    });
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn checker() -> RgbImage {
+        test_util::checker(37, 23)
+    }
+
+    #[test]
+    fn inactive_effects_leave_the_image_alone() {
+        let img = checker();
+        let config = CRTEffectsConfig {
+            scanline_intensity: 0.0,
+            curvature_strength: 0.0,
+            corner_radius: 0.0,
+            vignette_strength: 0.0,
+            mask_type: MaskType::SlotMask,
+            mask_intensity: 0.0,
+            bloom_intensity: 0.0,
+            halation_intensity: 0.0,
+            ..Default::default()
+        };
+        assert!(apply_crt_effects(&img, img.height(), config) == img);
+        assert!(apply_crt_effects(&img, img.height(), CRTEffectsConfig::default()) == img);
+    }
+
+    #[test]
+    fn flat_screen_samples_the_source_pixels() {
+        let img = checker();
+        let decode = |p: &Rgb<u8>| p.0.map(|c| c as f32);
+        for filter in [ResampleFilter::Nearest, ResampleFilter::Bilinear, ResampleFilter::Bicubic] {
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let (sx, sy) = curve(x as f32 + 0.5, y as f32 + 0.5, img.width(), img.height(), 0.0);
+                assert_eq!(sample(&img, sx, sy, filter, &decode), decode(pixel), "{:?} at {}, {}", filter, x, y);
+            }
+
+            // Full-height beams with hard edges light every row completely,
+            // so the whole effect pass has to give the source back
+            let config = CRTEffectsConfig {
+                scanline_intensity: 1.0,
+                scanline_thickness: 1.0,
+                scanline_softness: 0.0,
+                curvature_filter: filter,
+                ..Default::default()
+            };
+            assert!(apply_crt_effects(&img, img.height(), config) == img, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn composite_is_deterministic_and_crawls() {
+        let img = checker();
+        for preset in [CompositePreset::NTSC, CompositePreset::PAL, CompositePreset::RF] {
+            let first = apply_composite(&img, preset, 3);
+            assert!(first == apply_composite(&img, preset, 3), "{:?}", preset);
+            assert!(first != apply_composite(&img, preset, 4), "{:?}", preset);
+        }
+        assert!(apply_composite(&img, CompositePreset::None, 5) == img);
+    }
+}
@@ -0,0 +1,540 @@
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::color::lab_to_srgb;
+
+// Palette images with more colours than this are pictures, not palettes
+const MAX_IMAGE_COLORS: usize = 256;
+
+// Fixed sizes of the formats that have one
+const ACT_COLORS: usize = 256;
+const PAINT_NET_COLORS: usize = 96;
+
+/// Palette file formats understood by `PaletteFile::load` and `save`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaletteFormat {
+    Gpl,        // GIMP .gpl
+    PaintNet,   // Paint.NET .txt
+    Jasc,       // JASC / Paint Shop Pro .pal (also reads RIFF .pal)
+    Act,        // Adobe Color Table .act
+    Aco,        // Adobe Color Swatch .aco
+    Hex,        // One RRGGBB per line, as exported by Lospec
+    Png,        // Palette strip image, one colour per pixel
+}
+
+impl PaletteFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gpl" => Some(PaletteFormat::Gpl),
+            "txt" => Some(PaletteFormat::PaintNet),
+            "pal" => Some(PaletteFormat::Jasc),
+            "act" => Some(PaletteFormat::Act),
+            "aco" => Some(PaletteFormat::Aco),
+            "hex" => Some(PaletteFormat::Hex),
+            "png" => Some(PaletteFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// A named list of colours, as read from or written to a palette file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteFile {
+    pub name: String,
+    #[serde(with = "rgb_list")]
+    pub colors: Vec<Rgb<u8>>,
+}
+
+/// Serde adapter for `Vec<Rgb<u8>>` as `[[r, g, b], ...]`, since `Rgb` itself
+/// isn't serializable.
+pub mod rgb_list {
+    use image::Rgb;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(colors: &[Rgb<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        colors.iter().map(|c| c.0).collect::<Vec<[u8; 3]>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Rgb<u8>>, D::Error> {
+        Ok(Vec::<[u8; 3]>::deserialize(deserializer)?.into_iter().map(Rgb).collect())
+    }
+}
+
+impl PaletteFile {
+    /// Reads a palette file, picking the format from its extension. Formats
+    /// without a name field are named after the file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let path_ref = Path::new(path);
+        let format = PaletteFormat::from_path(path_ref)
+            .ok_or_else(|| format!("Unsupported palette file: {}", path))?;
+        let bytes = std::fs::read(path_ref)
+            .map_err(|e| format!("Failed to read palette {}: {}", path, e))?;
+
+        let mut palette = Self::parse(&bytes, format)
+            .map_err(|e| format!("Failed to read palette {}: {}", path, e))?;
+        if palette.name.is_empty() {
+            palette.name = path_ref.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette, picking the format from the file extension.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let format = PaletteFormat::from_path(Path::new(path))
+            .ok_or_else(|| format!("Unsupported palette file: {}", path))?;
+        let bytes = self.encode(format)?;
+        std::fs::write(path, bytes)
+            .map_err(|e| format!("Failed to write palette {}: {}", path, e))
+    }
+
+    /// Parses file contents. `name` is left empty when the format has none.
+    pub fn parse(bytes: &[u8], format: PaletteFormat) -> Result<Self, String> {
+        let (name, colors) = match format {
+            PaletteFormat::Gpl => parse_gpl(bytes)?,
+            PaletteFormat::PaintNet => (String::new(), parse_paint_net(bytes)?),
+            PaletteFormat::Jasc => (String::new(), parse_jasc(bytes)?),
+            PaletteFormat::Act => (String::new(), parse_act(bytes)?),
+            PaletteFormat::Aco => (String::new(), parse_aco(bytes)?),
+            PaletteFormat::Hex => (String::new(), parse_hex(bytes)?),
+            PaletteFormat::Png => (String::new(), parse_png(bytes)?),
+        };
+
+        if colors.is_empty() {
+            return Err("Palette contains no colours".to_string());
+        }
+        Ok(Self { name, colors })
+    }
+
+    /// Encodes the palette as file contents.
+    pub fn encode(&self, format: PaletteFormat) -> Result<Vec<u8>, String> {
+        if self.colors.is_empty() {
+            return Err("Palette contains no colours".to_string());
+        }
+
+        match format {
+            PaletteFormat::Gpl => Ok(self.encode_gpl()),
+            PaletteFormat::PaintNet => self.encode_paint_net(),
+            PaletteFormat::Jasc => Ok(self.encode_jasc()),
+            PaletteFormat::Act => self.encode_act(),
+            PaletteFormat::Aco => Ok(self.encode_aco()),
+            PaletteFormat::Hex => Ok(self.encode_hex()),
+            PaletteFormat::Png => self.encode_png(),
+        }
+    }
+
+    fn encode_gpl(&self) -> Vec<u8> {
+        let mut text = format!(
+            "GIMP Palette\nName: {}\nColumns: {}\n#\n",
+            self.name,
+            self.colors.len().min(16)
+        );
+        for c in &self.colors {
+            text.push_str(&format!("{:3} {:3} {:3}\t{}\n", c[0], c[1], c[2], hex_string(*c)));
+        }
+        text.into_bytes()
+    }
+
+    fn encode_paint_net(&self) -> Result<Vec<u8>, String> {
+        if self.colors.len() > PAINT_NET_COLORS {
+            return Err(format!("Paint.NET palettes hold at most {} colours", PAINT_NET_COLORS));
+        }
+
+        let mut text = format!("; paint.net Palette File\n; {}\n; Colors: {}\n", self.name, self.colors.len());
+        for c in &self.colors {
+            text.push_str(&format!("FF{:02X}{:02X}{:02X}\n", c[0], c[1], c[2]));
+        }
+        Ok(text.into_bytes())
+    }
+
+    fn encode_jasc(&self) -> Vec<u8> {
+        let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", self.colors.len());
+        for c in &self.colors {
+            text.push_str(&format!("{} {} {}\r\n", c[0], c[1], c[2]));
+        }
+        text.into_bytes()
+    }
+
+    fn encode_act(&self) -> Result<Vec<u8>, String> {
+        if self.colors.len() > ACT_COLORS {
+            return Err(format!("ACT palettes hold at most {} colours", ACT_COLORS));
+        }
+
+        // 256 RGB triples, zero padded, then the colour count and "no
+        // transparent index" as big-endian u16s
+        let mut bytes = vec![0u8; ACT_COLORS * 3];
+        for (i, c) in self.colors.iter().enumerate() {
+            bytes[i * 3..i * 3 + 3].copy_from_slice(&c.0);
+        }
+        bytes.extend_from_slice(&(self.colors.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&0xFFFFu16.to_be_bytes());
+        Ok(bytes)
+    }
+
+    fn encode_aco(&self) -> Vec<u8> {
+        // Version 1 (colours only) followed by version 2 (colours with names),
+        // as Photoshop writes it
+        let mut bytes = Vec::new();
+        for version in [1u16, 2] {
+            bytes.extend_from_slice(&version.to_be_bytes());
+            bytes.extend_from_slice(&(self.colors.len() as u16).to_be_bytes());
+
+            for c in &self.colors {
+                bytes.extend_from_slice(&0u16.to_be_bytes()); // RGB colour space
+                for channel in c.0 {
+                    bytes.extend_from_slice(&(channel as u16 * 257).to_be_bytes());
+                }
+                bytes.extend_from_slice(&0u16.to_be_bytes());
+
+                if version == 2 {
+                    let name: Vec<u16> = hex_string(*c).encode_utf16().chain([0]).collect();
+                    bytes.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                    for unit in name {
+                        bytes.extend_from_slice(&unit.to_be_bytes());
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    fn encode_hex(&self) -> Vec<u8> {
+        self.colors.iter()
+            .map(|c| format!("{:02x}{:02x}{:02x}\n", c[0], c[1], c[2]))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>, String> {
+        let strip = RgbImage::from_fn(self.colors.len() as u32, 1, |x, _| self.colors[x as usize]);
+        let mut bytes = Vec::new();
+        strip.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode palette image: {}", e))?;
+        Ok(bytes)
+    }
+}
+
+fn hex_string(c: Rgb<u8>) -> String {
+    format!("#{:02X}{:02X}{:02X}", c[0], c[1], c[2])
+}
+
+/// Parses "RRGGBB" (with or without a leading '#').
+pub fn parse_hex_color(token: &str) -> Option<Rgb<u8>> {
+    let token = token.trim();
+    let token = token.strip_prefix('#').unwrap_or(token);
+    // `from_str_radix` would also take a leading sign
+    if token.len() != 6 || !token.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(token, 16).ok()?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Some(Rgb([r, g, b]))
+}
+
+// First three integers of a line, e.g. "255 128 0" or "255 128 0\tOrange"
+fn parse_rgb_line(line: &str) -> Option<Rgb<u8>> {
+    let mut values = line.split_whitespace().map(|token| token.parse::<u8>());
+    let r = values.next()?.ok()?;
+    let g = values.next()?.ok()?;
+    let b = values.next()?.ok()?;
+    Some(Rgb([r, g, b]))
+}
+
+fn parse_gpl(bytes: &[u8]) -> Result<(String, Vec<Rgb<u8>>), String> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("GIMP Palette") {
+        return Err("Not a GIMP palette".to_string());
+    }
+
+    let mut name = String::new();
+    let mut colors = Vec::new();
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") || line.starts_with("Channels:") {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = value.trim().to_string();
+            continue;
+        }
+        colors.push(parse_rgb_line(line).ok_or_else(|| format!("Invalid colour line: {}", line))?);
+    }
+    Ok((name, colors))
+}
+
+fn parse_paint_net(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .map(|line| {
+            // AARRGGBB; alpha is dropped. Plain RRGGBB is accepted too. Checking
+            // the digits first keeps the slice on a character boundary.
+            let is_argb = line.len() == 8 && line.bytes().all(|b| b.is_ascii_hexdigit());
+            let rgb = if is_argb { &line[2..] } else { line };
+            parse_hex_color(rgb).ok_or_else(|| format!("Invalid colour line: {}", line))
+        })
+        .collect()
+}
+
+fn parse_jasc(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    if bytes.starts_with(b"RIFF") {
+        return parse_riff_pal(bytes);
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some("JASC-PAL") {
+        return Err("Not a JASC palette".to_string());
+    }
+    let _version = lines.next();
+    let count: usize = lines.next()
+        .and_then(|line| line.parse().ok())
+        .ok_or("Invalid JASC colour count")?;
+
+    let colors: Vec<Rgb<u8>> = lines
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .map(|line| parse_rgb_line(line).ok_or_else(|| format!("Invalid colour line: {}", line)))
+        .collect::<Result<_, _>>()?;
+    if colors.len() < count {
+        return Err(format!("Expected {} colours, found {}", count, colors.len()));
+    }
+    Ok(colors)
+}
+
+// Microsoft RIFF palette: "RIFF" size "PAL " "data" size, version, count, then
+// count x (r, g, b, flags)
+fn parse_riff_pal(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    if bytes.len() < 24 || &bytes[8..12] != b"PAL " || &bytes[12..16] != b"data" {
+        return Err("Not a RIFF palette".to_string());
+    }
+    let count = u16::from_le_bytes([bytes[22], bytes[23]]) as usize;
+    let entries = bytes.get(24..24 + count * 4).ok_or("Truncated RIFF palette")?;
+    Ok(entries.chunks_exact(4).map(|e| Rgb([e[0], e[1], e[2]])).collect())
+}
+
+fn parse_act(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    if bytes.len() < ACT_COLORS * 3 {
+        return Err("Truncated ACT palette".to_string());
+    }
+
+    // The optional trailer holds the number of colours actually used
+    let count = match bytes.get(ACT_COLORS * 3..ACT_COLORS * 3 + 2) {
+        Some(&[hi, lo]) => (u16::from_be_bytes([hi, lo]) as usize).clamp(1, ACT_COLORS),
+        _ => ACT_COLORS,
+    };
+    Ok(bytes[..count * 3].chunks_exact(3).map(|c| Rgb([c[0], c[1], c[2]])).collect())
+}
+
+fn parse_aco(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    let read_u16 = |offset: usize| -> Result<u16, String> {
+        bytes.get(offset..offset + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| "Truncated ACO swatches".to_string())
+    };
+
+    // Version 1 and version 2 sections list the same colours, so the first
+    // one is enough
+    let version = read_u16(0)?;
+    if version != 1 && version != 2 {
+        return Err("Not an ACO swatch file".to_string());
+    }
+    let count = read_u16(2)? as usize;
+
+    let mut colors = Vec::with_capacity(count);
+    let mut offset = 4;
+    for _ in 0..count {
+        let space = read_u16(offset)?;
+        let w = read_u16(offset + 2)?;
+        let x = read_u16(offset + 4)?;
+        let y = read_u16(offset + 6)?;
+        let z = read_u16(offset + 8)?;
+        offset += 10;
+
+        if version == 2 {
+            // UTF-16 name: u32 length in code units, then the string
+            let name_len = bytes.get(offset..offset + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or("Truncated ACO swatches")?;
+            offset += 4 + name_len * 2;
+        }
+
+        colors.push(aco_color(space, w, x, y, z)?);
+    }
+    Ok(colors)
+}
+
+fn aco_color(space: u16, w: u16, x: u16, y: u16, z: u16) -> Result<Rgb<u8>, String> {
+    let unit = |v: u16| v as f32 / 65535.0;
+    let to_u8 = |v: f32| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    match space {
+        // RGB, 0..65535 per channel
+        0 => Ok(Rgb([(w >> 8) as u8, (x >> 8) as u8, (y >> 8) as u8])),
+        // HSB, hue 0..65535 for 0..360 degrees
+        1 => {
+            let (h, s, v) = (unit(w) * 6.0, unit(x), unit(y));
+            let c = v * s;
+            let m = v - c;
+            let k = c * (1.0 - (h % 2.0 - 1.0).abs());
+            let (r, g, b) = match h as u32 {
+                0 => (c, k, 0.0),
+                1 => (k, c, 0.0),
+                2 => (0.0, c, k),
+                3 => (0.0, k, c),
+                4 => (k, 0.0, c),
+                _ => (c, 0.0, k),
+            };
+            Ok(Rgb([to_u8(r + m), to_u8(g + m), to_u8(b + m)]))
+        }
+        // CMYK, stored inverted: 0 is full ink
+        2 => {
+            let k = unit(z);
+            Ok(Rgb([to_u8(unit(w) * k), to_u8(unit(x) * k), to_u8(unit(y) * k)]))
+        }
+        // Lab, L in 0..10000, a and b signed in hundredths
+        7 => Ok(lab_to_srgb([w as f32 / 100.0, x as i16 as f32 / 100.0, y as i16 as f32 / 100.0])),
+        // Grayscale, 0..10000 of black ink
+        8 => Ok(Rgb([to_u8(1.0 - w.min(10000) as f32 / 10000.0); 3])),
+        _ => Err(format!("Unsupported ACO colour space {}", space)),
+    }
+}
+
+fn parse_hex(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with("//"))
+        .map(|line| parse_hex_color(line).ok_or_else(|| format!("Invalid colour line: {}", line)))
+        .collect()
+}
+
+fn parse_png(bytes: &[u8]) -> Result<Vec<Rgb<u8>>, String> {
+    let img = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to decode palette image: {}", e))?
+        .to_rgb8();
+
+    // Distinct colours in reading order, so both 1px strips and grids of
+    // larger swatches come out in their visual order
+    let mut colors: Vec<Rgb<u8>> = Vec::new();
+    let mut seen = HashSet::new();
+    for pixel in img.pixels() {
+        if seen.insert(*pixel) {
+            if colors.len() == MAX_IMAGE_COLORS {
+                return Err(format!("Palette image has more than {} colours", MAX_IMAGE_COLORS));
+            }
+            colors.push(*pixel);
+        }
+    }
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PaletteFile {
+        PaletteFile {
+            name: "Sample".to_string(),
+            colors: vec![Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([255, 128, 0]), Rgb([12, 34, 56])],
+        }
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let palette = sample();
+        for format in [
+            PaletteFormat::Gpl,
+            PaletteFormat::PaintNet,
+            PaletteFormat::Jasc,
+            PaletteFormat::Act,
+            PaletteFormat::Aco,
+            PaletteFormat::Hex,
+            PaletteFormat::Png,
+        ] {
+            let bytes = palette.encode(format).unwrap();
+            let parsed = PaletteFile::parse(&bytes, format).unwrap();
+            assert_eq!(parsed.colors, palette.colors, "{:?}", format);
+            if format == PaletteFormat::Gpl {
+                assert_eq!(parsed.name, palette.name);
+            }
+        }
+    }
+
+    #[test]
+    fn full_act_round_trips() {
+        let palette = PaletteFile {
+            name: String::new(),
+            colors: (0..=255).map(|i| Rgb([i as u8, 255 - i as u8, 7])).collect(),
+        };
+        let bytes = palette.encode(PaletteFormat::Act).unwrap();
+        assert_eq!(PaletteFile::parse(&bytes, PaletteFormat::Act).unwrap().colors, palette.colors);
+    }
+
+    #[test]
+    fn riff_pal_is_read() {
+        let colors = sample().colors;
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(16 + colors.len() as u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(b"PAL data");
+        bytes.extend_from_slice(&(4 + colors.len() as u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(&0x0300u16.to_le_bytes());
+        bytes.extend_from_slice(&(colors.len() as u16).to_le_bytes());
+        for c in &colors {
+            bytes.extend_from_slice(&[c[0], c[1], c[2], 0]);
+        }
+        assert_eq!(PaletteFile::parse(&bytes, PaletteFormat::Jasc).unwrap().colors, colors);
+
+        // Fewer entries than the header claims
+        assert!(PaletteFile::parse(&bytes[..bytes.len() - 1], PaletteFormat::Jasc).is_err());
+        assert!(PaletteFile::parse(&bytes[..20], PaletteFormat::Jasc).is_err());
+    }
+
+    #[test]
+    fn truncated_binary_palettes_are_rejected() {
+        let palette = sample();
+
+        let act = palette.encode(PaletteFormat::Act).unwrap();
+        for len in [0, 1, 3, ACT_COLORS * 3 - 1] {
+            assert!(PaletteFile::parse(&act[..len], PaletteFormat::Act).is_err(), "ACT {}", len);
+        }
+
+        let aco = palette.encode(PaletteFormat::Aco).unwrap();
+        for len in [0, 1, 3, 4, 13] {
+            assert!(PaletteFile::parse(&aco[..len], PaletteFormat::Aco).is_err(), "ACO {}", len);
+        }
+        // A version 2 header whose name runs past the end of the file
+        let mut aco_v2 = vec![0, 2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        aco_v2.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(PaletteFile::parse(&aco_v2[..10], PaletteFormat::Aco).is_err());
+
+        assert!(PaletteFile::parse(b"RIFF", PaletteFormat::Jasc).is_err());
+    }
+
+    #[test]
+    fn malformed_text_lines_are_rejected() {
+        for line in ["é123456", "FFé12345", "+12345", "##ABCDEF", "-1234567", "12345G", "1234567"] {
+            assert!(PaletteFile::parse(line.as_bytes(), PaletteFormat::PaintNet).is_err(), "{}", line);
+            assert!(PaletteFile::parse(line.as_bytes(), PaletteFormat::Hex).is_err(), "{}", line);
+        }
+        assert!(PaletteFile::parse(b"GIMP Palette\n255 0\n", PaletteFormat::Gpl).is_err());
+        assert!(PaletteFile::parse(b"JASC-PAL\n0100\n2\n1 2 3\n", PaletteFormat::Jasc).is_err());
+    }
+
+    #[test]
+    fn hex_colors_parse() {
+        assert_eq!(parse_hex_color("#FF8000"), Some(Rgb([255, 128, 0])));
+        assert_eq!(parse_hex_color(" ff8000 "), Some(Rgb([255, 128, 0])));
+        assert_eq!(parse_hex_color("+12345"), None);
+        assert_eq!(parse_hex_color("##ABCDEF"), None);
+        assert_eq!(parse_hex_color("#ABCDE"), None);
+    }
+}
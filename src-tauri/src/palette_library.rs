@@ -0,0 +1,264 @@
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::palette_file::PaletteFile;
+use crate::pipeline::PipelineConfig;
+
+/// A palette saved in the user's library.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryPalette {
+    pub id: String,
+    #[serde(flatten)]
+    pub palette: PaletteFile,
+}
+
+/// User palettes stored as one JSON file per palette (`<id>.json`) in a
+/// directory, typically `<app data>/palettes`.
+pub struct PaletteLibrary {
+    dir: PathBuf,
+}
+
+impl PaletteLibrary {
+    /// The directory is created on the first write.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Every palette in the library, oldest first. Files that fail to parse
+    /// are skipped rather than hiding the rest of the library.
+    pub fn list(&self) -> Result<Vec<LibraryPalette>, String> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read palette library: {}", e)),
+        };
+
+        let mut palettes: Vec<LibraryPalette> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| match read_palette(&path) {
+                Ok(palette) => Some(palette),
+                Err(e) => {
+                    log::warn!("Skipping library palette {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+        // IDs start with the creation time
+        palettes.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(palettes)
+    }
+
+    pub fn get(&self, id: &str) -> Result<LibraryPalette, String> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Err(format!("Palette {} is not in the library", id));
+        }
+        read_palette(&path)
+    }
+
+    pub fn create(&self, name: &str, colors: Vec<Rgb<u8>>) -> Result<LibraryPalette, String> {
+        validate_colors(&colors)?;
+        let palette = LibraryPalette {
+            id: self.new_id()?,
+            palette: PaletteFile { name: name.trim().to_string(), colors },
+        };
+        self.write(&palette)?;
+        Ok(palette)
+    }
+
+    pub fn rename(&self, id: &str, name: &str) -> Result<LibraryPalette, String> {
+        let mut palette = self.get(id)?;
+        palette.palette.name = name.trim().to_string();
+        self.write(&palette)?;
+        Ok(palette)
+    }
+
+    pub fn duplicate(&self, id: &str) -> Result<LibraryPalette, String> {
+        let original = self.get(id)?;
+        self.create(&format!("{} copy", original.palette.name), original.palette.colors)
+    }
+
+    /// Replaces the palette's colours.
+    pub fn edit(&self, id: &str, colors: Vec<Rgb<u8>>) -> Result<LibraryPalette, String> {
+        validate_colors(&colors)?;
+        let mut palette = self.get(id)?;
+        palette.palette.colors = colors;
+        self.write(&palette)?;
+        Ok(palette)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        std::fs::remove_file(self.path(id)?)
+            .map_err(|e| format!("Failed to delete palette {}: {}", id, e))
+    }
+
+    /// Swaps `config.palette_id` for the library palette's colours, so the
+    /// config can be handed to `Pipeline::new`.
+    pub fn resolve(&self, config: &mut PipelineConfig) -> Result<(), String> {
+        if let Some(id) = config.palette_id.take() {
+            config.custom_palette = Some(self.get(&id)?.palette);
+        }
+        Ok(())
+    }
+
+    // IDs become file names, so anything but [0-9a-z-] is rejected, which
+    // also keeps ids like "../x" from escaping the library directory
+    fn path(&self, id: &str) -> Result<PathBuf, String> {
+        let valid = !id.is_empty()
+            && id.chars().all(|c| c.is_ascii_digit() || c.is_ascii_lowercase() || c == '-');
+        if !valid {
+            return Err(format!("Invalid palette id: {}", id));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    // Creation time in hex, bumped past any existing id
+    fn new_id(&self) -> Result<String, String> {
+        let mut stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros())
+            .unwrap_or(0);
+        loop {
+            let id = format!("{:014x}", stamp);
+            if !self.path(&id)?.exists() {
+                return Ok(id);
+            }
+            stamp += 1;
+        }
+    }
+
+    // Written to a temporary file first, so a crash mid-write can't leave a
+    // truncated palette behind
+    fn write(&self, palette: &LibraryPalette) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create palette library: {}", e))?;
+
+        let path = self.path(&palette.id)?;
+        let temp = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(palette)
+            .map_err(|e| format!("Failed to serialize palette: {}", e))?;
+        std::fs::write(&temp, json)
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|e| format!("Failed to save palette {}: {}", palette.id, e))
+    }
+}
+
+fn read_palette(path: &Path) -> Result<LibraryPalette, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn validate_colors(colors: &[Rgb<u8>]) -> Result<(), String> {
+    if colors.is_empty() {
+        return Err("A palette needs at least one colour".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    // A library in a fresh temporary directory, removed when dropped
+    struct TempLibrary(PaletteLibrary);
+
+    impl TempLibrary {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("pixelforge-library-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(PaletteLibrary::new(dir))
+        }
+    }
+
+    impl Drop for TempLibrary {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0.dir);
+        }
+    }
+
+    #[test]
+    fn palettes_round_trip() {
+        let library = TempLibrary::new("round-trip");
+        let library = &library.0;
+        assert!(library.list().unwrap().is_empty());
+
+        let created = library.create("  Sunset ", vec![RED, BLUE]).unwrap();
+        assert_eq!(created.palette.name, "Sunset");
+        assert_eq!(library.get(&created.id).unwrap(), created);
+
+        let renamed = library.rename(&created.id, "Dusk").unwrap();
+        assert_eq!((renamed.id.as_str(), renamed.palette.name.as_str()), (created.id.as_str(), "Dusk"));
+
+        let copy = library.duplicate(&created.id).unwrap();
+        assert_ne!(copy.id, created.id);
+        assert_eq!(copy.palette.name, "Dusk copy");
+        assert_eq!(copy.palette.colors, [RED, BLUE]);
+
+        let edited = library.edit(&copy.id, vec![BLUE]).unwrap();
+        assert_eq!(library.get(&copy.id).unwrap(), edited);
+        assert!(library.edit(&copy.id, Vec::new()).is_err());
+        assert!(library.create("Empty", Vec::new()).is_err());
+
+        // Oldest first
+        assert_eq!(library.list().unwrap(), [renamed.clone(), edited]);
+
+        library.delete(&copy.id).unwrap();
+        assert!(library.get(&copy.id).is_err());
+        assert!(library.delete(&copy.id).is_err());
+        assert_eq!(library.list().unwrap(), [renamed]);
+    }
+
+    #[test]
+    fn ids_cannot_leave_the_library() {
+        let library = TempLibrary::new("traversal");
+        let library = &library.0;
+        let palette = library.create("Kept", vec![RED]).unwrap();
+
+        // A palette file beside the library directory
+        let outside = library.dir.with_file_name(format!("pixelforge-outside-{}", std::process::id()));
+        std::fs::write(outside.with_extension("json"), "{}").unwrap();
+        let escape = format!("../{}", outside.file_name().unwrap().to_str().unwrap());
+
+        for id in [escape.as_str(), "..", "a/b", "/tmp/x", "..\\x", "x.json", "UPPER", ""] {
+            assert!(library.get(id).is_err(), "{:?}", id);
+            assert!(library.rename(id, "x").is_err(), "{:?}", id);
+            assert!(library.duplicate(id).is_err(), "{:?}", id);
+            assert!(library.edit(id, vec![BLUE]).is_err(), "{:?}", id);
+            assert!(library.delete(id).is_err(), "{:?}", id);
+        }
+        assert!(outside.with_extension("json").exists());
+        let _ = std::fs::remove_file(outside.with_extension("json"));
+        assert_eq!(library.list().unwrap(), [palette]);
+    }
+
+    #[test]
+    fn resolve_swaps_the_id_for_the_palette() {
+        let library = TempLibrary::new("resolve");
+        let library = &library.0;
+        let palette = library.create("Two", vec![RED, BLUE]).unwrap();
+
+        let mut config = PipelineConfig { palette_id: Some(palette.id.clone()), ..Default::default() };
+        library.resolve(&mut config).unwrap();
+        assert_eq!(config.palette_id, None);
+        assert_eq!(config.custom_palette, Some(palette.palette));
+
+        for id in ["0123456789abcd", "../two"] {
+            let mut config = PipelineConfig { palette_id: Some(id.to_string()), ..Default::default() };
+            assert!(library.resolve(&mut config).is_err(), "{:?}", id);
+        }
+
+        // Nothing to resolve
+        let mut config = PipelineConfig::default();
+        library.resolve(&mut config).unwrap();
+        assert_eq!(config.custom_palette, None);
+    }
+}
@@ -1,11 +1,172 @@
-use image::Rgb;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Clone, Copy, Debug)]
+use crate::color::{BitDepth, ColorMetric, ColorTarget, PaletteMatcher, srgb_to_oklab};
+use crate::quantize::{PaletteAlgorithm, extract_palette};
+
+// Refinement rounds for the entries `subset_palette` picks
+const SUBSET_ROUNDS: usize = 8;
+
+// OKLab chroma below which `PaletteOrder::Hue` treats a colour as grey
+const GREY_CHROMA: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum PaletteName {
     GameBoy,
+    GameBoyColor,
+    VirtualBoy,
     NES,
+    MasterSystem,
+    MegaDrive,
+    SNES,
     CGA,
+    EGA,
+    PC98,
+    C64,
+    ZXSpectrum,
+    AmstradCPC,
+    MSX,
+    AppleII,
+    Teletext,
     Pico8,
+    Sweetie16,
+    DawnBringer16,
+    Endesga32,
+}
+
+/// What `list_palettes` reports about a preset.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteInfo {
+    pub id: &'static str, // the name `from_name` accepts, e.g. "ZXSpectrum"
+    pub name: &'static str,
+    pub platform: &'static str,
+    pub color_count: usize,
+}
+
+impl PaletteName {
+    pub const ALL: [PaletteName; 20] = [
+        PaletteName::GameBoy,
+        PaletteName::GameBoyColor,
+        PaletteName::VirtualBoy,
+        PaletteName::NES,
+        PaletteName::MasterSystem,
+        PaletteName::MegaDrive,
+        PaletteName::SNES,
+        PaletteName::CGA,
+        PaletteName::EGA,
+        PaletteName::PC98,
+        PaletteName::C64,
+        PaletteName::ZXSpectrum,
+        PaletteName::AmstradCPC,
+        PaletteName::MSX,
+        PaletteName::AppleII,
+        PaletteName::Teletext,
+        PaletteName::Pico8,
+        PaletteName::Sweetie16,
+        PaletteName::DawnBringer16,
+        PaletteName::Endesga32,
+    ];
+
+    /// Looks up a preset by the name the frontend uses, e.g. "GameBoy".
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.id() == name)
+    }
+
+    pub fn id(self) -> &'static str {
+        self.describe().0
+    }
+
+    pub fn info(self) -> PaletteInfo {
+        let (id, name, platform, color_count) = self.describe();
+        PaletteInfo { id, name, platform, color_count }
+    }
+
+    /// What frames are quantized to with this preset. Uniform hardware colour
+    /// spaces snap to their grid instead of searching every colour of it.
+    pub fn target(self) -> ColorTarget {
+        match self {
+            PaletteName::MasterSystem => ColorTarget::Grid(BitDepth::uniform(2)),
+            PaletteName::MegaDrive => ColorTarget::Grid(BitDepth::uniform(3)),
+            PaletteName::SNES => ColorTarget::Grid(BitDepth::uniform(5)),
+            _ => ColorTarget::Palette(get_palette(self)),
+        }
+    }
+
+    // (id, display name, platform, number of entries in `get_palette`)
+    fn describe(self) -> (&'static str, &'static str, &'static str, usize) {
+        match self {
+            PaletteName::GameBoy => ("GameBoy", "Game Boy", "Nintendo Game Boy", 4),
+            PaletteName::GameBoyColor => ("GameBoyColor", "Game Boy Color (15-bit)", "Nintendo Game Boy Color", 32761),
+            PaletteName::VirtualBoy => ("VirtualBoy", "Virtual Boy", "Nintendo Virtual Boy", 4),
+            PaletteName::NES => ("NES", "NES", "Nintendo Entertainment System", 64),
+            PaletteName::MasterSystem => ("MasterSystem", "Master System", "Sega Master System", 64),
+            PaletteName::MegaDrive => ("MegaDrive", "Mega Drive (9-bit)", "Sega Mega Drive / Genesis", 512),
+            PaletteName::SNES => ("SNES", "SNES (15-bit)", "Super Nintendo", 32768),
+            PaletteName::CGA => ("CGA", "CGA", "IBM PC (CGA)", 16),
+            PaletteName::EGA => ("EGA", "EGA", "IBM PC (EGA)", 16),
+            PaletteName::PC98 => ("PC98", "PC-98", "NEC PC-9801", 16),
+            PaletteName::C64 => ("C64", "C64", "Commodore 64", 16),
+            PaletteName::ZXSpectrum => ("ZXSpectrum", "ZX Spectrum", "Sinclair ZX Spectrum", 15),
+            PaletteName::AmstradCPC => ("AmstradCPC", "Amstrad CPC", "Amstrad CPC", 27),
+            PaletteName::MSX => ("MSX", "MSX", "MSX (TMS9918)", 15),
+            PaletteName::AppleII => ("AppleII", "Apple II", "Apple II (lo-res)", 15),
+            PaletteName::Teletext => ("Teletext", "Teletext", "Teletext / BBC Micro", 8),
+            PaletteName::Pico8 => ("Pico8", "PICO-8", "PICO-8 fantasy console", 16),
+            PaletteName::Sweetie16 => ("Sweetie16", "Sweetie-16", "Pixel art (GrafxKid)", 16),
+            PaletteName::DawnBringer16 => ("DawnBringer16", "DawnBringer 16", "Pixel art (DawnBringer)", 16),
+            PaletteName::Endesga32 => ("Endesga32", "Endesga-32", "Pixel art (Endesga)", 32),
+        }
+    }
+}
+
+/// Metadata of every preset, in menu order.
+pub fn list_palettes() -> Vec<PaletteInfo> {
+    PaletteName::ALL.into_iter().map(PaletteName::info).collect()
+}
+
+// Palettes given as 0xRRGGBB
+fn hex(colors: &[u32]) -> Vec<Rgb<u8>> {
+    colors.iter().map(|&c| Rgb([(c >> 16) as u8, (c >> 8) as u8, c as u8])).collect()
+}
+
+// Every combination of `levels` per channel
+fn rgb_grid(levels: &[u8]) -> Vec<Rgb<u8>> {
+    let mut colors = Vec::with_capacity(levels.len().pow(3));
+    for &r in levels {
+        for &g in levels {
+            for &b in levels {
+                colors.push(Rgb([r, g, b]));
+            }
+        }
+    }
+    colors
+}
+
+// The Game Boy Color's 15-bit colours as its LCD shows them: washed out and
+// with channels bleeding into each other (the colour correction most
+// emulators use), with duplicates removed
+fn game_boy_color() -> Vec<Rgb<u8>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut colors = Vec::new();
+    for r in 0..32u32 {
+        for g in 0..32u32 {
+            for b in 0..32u32 {
+                let mix = |x: u32| (x.min(960) >> 2) as u8;
+                let color = Rgb([
+                    mix(r * 26 + g * 4 + b * 2),
+                    mix(g * 24 + b * 8),
+                    mix(r * 6 + g * 4 + b * 22),
+                ]);
+                if seen.insert(color) {
+                    colors.push(color);
+                }
+            }
+        }
+    }
+    colors
 }
 
 pub fn get_palette(name: PaletteName) -> Vec<Rgb<u8>> {
@@ -43,6 +204,50 @@ pub fn get_palette(name: PaletteName) -> Vec<Rgb<u8>> {
             Rgb([85, 85, 85]), Rgb([85, 85, 255]), Rgb([85, 255, 85]), Rgb([85, 255, 255]),
             Rgb([255, 85, 85]), Rgb([255, 85, 255]), Rgb([255, 255, 85]), Rgb([255, 255, 255]),
         ],
+        PaletteName::GameBoyColor => game_boy_color(),
+        PaletteName::VirtualBoy => vec![
+            Rgb([0, 0, 0]), Rgb([82, 0, 0]), Rgb([164, 0, 0]), Rgb([239, 0, 0]),
+        ],
+        // 2 bits per channel
+        PaletteName::MasterSystem => BitDepth::uniform(2).colors(),
+        // 3 bits per channel
+        PaletteName::MegaDrive => BitDepth::uniform(3).colors(),
+        // 5 bits per channel, 32768 colours
+        PaletteName::SNES => BitDepth::uniform(5).colors(),
+        // Default 16-colour RGBI palette, with brown instead of dark yellow
+        PaletteName::EGA => hex(&[
+            0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA,
+            0x555555, 0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+        ]),
+        // Default 16-colour palette: the 8 digital colours dimmed, then at full
+        // brightness, with grey for bright black
+        PaletteName::PC98 => hex(&[
+            0x000000, 0x000077, 0x770000, 0x770077, 0x007700, 0x007777, 0x777700, 0x777777,
+            0x444444, 0x0000FF, 0xFF0000, 0xFF00FF, 0x00FF00, 0x00FFFF, 0xFFFF00, 0xFFFFFF,
+        ]),
+        // Pepto's measured VIC-II colours
+        PaletteName::C64 => hex(&[
+            0x000000, 0xFFFFFF, 0x68372B, 0x70A4B2, 0x6F3D86, 0x588D43, 0x352879, 0xB8C76F,
+            0x6F4F25, 0x433900, 0x9A6759, 0x444444, 0x6C6C6C, 0x9AD284, 0x6C5EB5, 0x959595,
+        ]),
+        // Normal and BRIGHT; black is the same in both, so 15 colours
+        PaletteName::ZXSpectrum => hex(&[
+            0x000000, 0x0000D7, 0xD70000, 0xD700D7, 0x00D700, 0x00D7D7, 0xD7D700, 0xD7D7D7,
+            0x0000FF, 0xFF0000, 0xFF00FF, 0x00FF00, 0x00FFFF, 0xFFFF00, 0xFFFFFF,
+        ]),
+        // Three levels per channel, 27 colours
+        PaletteName::AmstradCPC => rgb_grid(&[0, 128, 255]),
+        // TMS9918 without the transparent entry
+        PaletteName::MSX => hex(&[
+            0x000000, 0x21C842, 0x5EDC78, 0x5455ED, 0x7D76FC, 0xD4524D, 0x42EBF5, 0xFC5554,
+            0xFF7978, 0xD4C154, 0xE6CE80, 0x21B03B, 0xC95BBA, 0xCCCCCC, 0xFFFFFF,
+        ]),
+        // Lo-res colours; the two greys are identical, so 15 colours
+        PaletteName::AppleII => hex(&[
+            0x000000, 0x901740, 0x402CA5, 0xD043E5, 0x006940, 0x808080, 0x2F95E5, 0xBFABFF,
+            0x405400, 0xD06A1A, 0xFF96BF, 0x2FBC1A, 0xBFD35A, 0x6FE8BF, 0xFFFFFF,
+        ]),
+        PaletteName::Teletext => BitDepth::uniform(1).colors(),
         PaletteName::Pico8 => vec![
             Rgb([0, 0, 0]),       // Black
             Rgb([29, 43, 83]),    // Dark Blue
@@ -61,5 +266,269 @@ pub fn get_palette(name: PaletteName) -> Vec<Rgb<u8>> {
             Rgb([255, 119, 168]), // Pink
             Rgb([255, 204, 170]), // Peach
         ],
+        PaletteName::Sweetie16 => hex(&[
+            0x1A1C2C, 0x5D275D, 0xB13E53, 0xEF7D57, 0xFFCD75, 0xA7F070, 0x38B764, 0x257179,
+            0x29366F, 0x3B5DC9, 0x41A6F6, 0x73EFF7, 0xF4F4F4, 0x94B0C2, 0x566C86, 0x333C57,
+        ]),
+        PaletteName::DawnBringer16 => hex(&[
+            0x140C1C, 0x442434, 0x30346D, 0x4E4A4E, 0x854C30, 0x346524, 0xD04648, 0x757161,
+            0x597DCE, 0xD27D2C, 0x8595A1, 0x6DAA2C, 0xD2AA99, 0x6DC2CA, 0xDAD45E, 0xDEEED6,
+        ]),
+        PaletteName::Endesga32 => hex(&[
+            0xBE4A2F, 0xD77643, 0xEAD4AA, 0xE4A672, 0xB86F50, 0x733E39, 0x3E2731, 0xA22633,
+            0xE43B44, 0xF77622, 0xFEAE34, 0xFEE761, 0x63C74D, 0x3E8948, 0x265C42, 0x193C3E,
+            0x124E89, 0x0099DB, 0x2CE8F5, 0xFFFFFF, 0xC0CBDC, 0x8B9BB4, 0x5A6988, 0x3A4466,
+            0x262B44, 0x181425, 0xFF0044, 0x68386C, 0xB55088, 0xF6757A, 0xE8B796, 0xC28569,
+        ]),
+    }
+}
+
+/// How `sort_palette` and `remap_palette` order colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaletteOrder {
+    #[default]
+    Luminance, // dark to light (OKLab lightness)
+    Hue,       // greys first, then around the colour wheel, dark to light within a hue
+}
+
+// (group, angle, lightness), compared in that order
+fn sort_key(color: Rgb<u8>, order: PaletteOrder) -> (u8, f32, f32) {
+    let [l, a, b] = srgb_to_oklab(color);
+    match order {
+        PaletteOrder::Luminance => (0, 0.0, l),
+        PaletteOrder::Hue if a.hypot(b) < GREY_CHROMA => (0, 0.0, l),
+        PaletteOrder::Hue => (1, b.atan2(a).rem_euclid(std::f32::consts::TAU), l),
+    }
+}
+
+// Indices of `colors` sorted by `order`, stable
+fn ranking(colors: &[Rgb<u8>], order: PaletteOrder) -> Vec<usize> {
+    let keys: Vec<(u8, f32, f32)> = colors.iter().map(|&c| sort_key(c, order)).collect();
+    let mut ranked: Vec<usize> = (0..colors.len()).collect();
+    ranked.sort_by(|&a, &b| {
+        let (ka, kb) = (keys[a], keys[b]);
+        ka.0.cmp(&kb.0).then(ka.1.total_cmp(&kb.1)).then(ka.2.total_cmp(&kb.2))
+    });
+    ranked
+}
+
+/// `colors` sorted by `order`. Colours that compare equal keep their order.
+pub fn sort_palette(colors: &[Rgb<u8>], order: PaletteOrder) -> Vec<Rgb<u8>> {
+    ranking(colors, order).into_iter().map(|i| colors[i]).collect()
+}
+
+/// `colors` without repeats, keeping the first of each.
+pub fn dedupe_palette(colors: &[Rgb<u8>]) -> Vec<Rgb<u8>> {
+    let mut unique: Vec<Rgb<u8>> = Vec::with_capacity(colors.len());
+    for &color in colors {
+        if !unique.contains(&color) {
+            unique.push(color);
+        }
+    }
+    unique
+}
+
+/// The colours of all `palettes` in order, without repeats.
+pub fn merge_palettes(palettes: &[Vec<Rgb<u8>>]) -> Vec<Rgb<u8>> {
+    dedupe_palette(&palettes.concat())
+}
+
+/// The (at most) `count` entries of `colors` that best fit `frames`, in palette
+/// order. Only entries the frames would use with the whole palette are picked,
+/// so there may be fewer. Starts from the entries closest to a Wu palette of
+/// the frames, then moves each pick to the middle of the entries it stands for.
+pub fn subset_palette(colors: &[Rgb<u8>], frames: &[RgbImage], count: usize, metric: ColorMetric) -> Vec<Rgb<u8>> {
+    let palette = dedupe_palette(colors);
+    if palette.len() <= count {
+        return palette;
+    }
+
+    // Pixels each entry gets when the frames use the whole palette
+    let mut histogram: HashMap<Rgb<u8>, u64> = HashMap::new();
+    for frame in frames {
+        for pixel in frame.pixels() {
+            *histogram.entry(*pixel).or_default() += 1;
+        }
+    }
+    let matcher = PaletteMatcher::new(&palette, metric);
+    let mut usage: HashMap<Rgb<u8>, u64> = HashMap::new();
+    for (color, n) in histogram {
+        *usage.entry(matcher.nearest(color)).or_default() += n;
+    }
+    // (colour, point in metric space, pixels), in palette order
+    let used: Vec<(Rgb<u8>, [f32; 3], f64)> = palette.iter()
+        .filter_map(|c| usage.get(c).map(|&n| (*c, metric.to_space(*c), n as f64)))
+        .collect();
+    if used.len() <= count {
+        return used.into_iter().map(|(c, _, _)| c).collect();
+    }
+
+    let nearest = |point: [f32; 3], among: &mut dyn Iterator<Item = usize>| {
+        among.min_by(|&a, &b| metric.distance(point, used[a].1).total_cmp(&metric.distance(point, used[b].1)))
+    };
+
+    let mut chosen: Vec<usize> = Vec::with_capacity(count);
+    for seed in extract_palette(frames, count, PaletteAlgorithm::Wu) {
+        let point = metric.to_space(seed);
+        if let Some(i) = nearest(point, &mut (0..used.len()).filter(|i| !chosen.contains(i))) {
+            chosen.push(i);
+        }
+    }
+    // Seeds that collided: top up with the most used entries
+    let mut by_usage: Vec<usize> = (0..used.len()).collect();
+    by_usage.sort_by(|&a, &b| used[b].2.total_cmp(&used[a].2));
+    for i in by_usage {
+        if chosen.len() >= count {
+            break;
+        }
+        if !chosen.contains(&i) {
+            chosen.push(i);
+        }
+    }
+
+    for _ in 0..SUBSET_ROUNDS {
+        // Weighted mean of the entries closest to each pick
+        let mut sums = vec![([0f64; 3], 0f64); chosen.len()];
+        for (_, point, weight) in &used {
+            let slot = (0..chosen.len())
+                .min_by(|&a, &b| {
+                    metric.distance(*point, used[chosen[a]].1).total_cmp(&metric.distance(*point, used[chosen[b]].1))
+                })
+                .expect("at least one pick");
+            for (sum, value) in sums[slot].0.iter_mut().zip(point) {
+                *sum += *value as f64 * weight;
+            }
+            sums[slot].1 += weight;
+        }
+
+        let mut next: Vec<usize> = Vec::with_capacity(chosen.len());
+        for (slot, (sum, weight)) in sums.iter().enumerate() {
+            let mean = sum.map(|s| (s / weight.max(f64::MIN_POSITIVE)) as f32);
+            let others: Vec<usize> = chosen.iter().chain(&next).copied().collect();
+            let pick = nearest(mean, &mut (0..used.len()).filter(|i| *i == chosen[slot] || !others.contains(i)))
+                .unwrap_or(chosen[slot]);
+            next.push(pick);
+        }
+        if next == chosen {
+            break;
+        }
+        chosen = next;
+    }
+
+    chosen.sort_unstable();
+    chosen.into_iter().map(|i| used[i].0).collect()
+}
+
+/// `to`'s colours lined up with `from`: each entry becomes the colour of `to`
+/// at the same rank under `order`, e.g. the darkest colour of `from` is
+/// replaced by the darkest of `to`. Ranks are stretched when the sizes differ,
+/// so the result always has `from.len()` entries (none if `to` is empty).
+pub fn remap_palette(from: &[Rgb<u8>], to: &[Rgb<u8>], order: PaletteOrder) -> Vec<Rgb<u8>> {
+    if to.is_empty() {
+        return Vec::new();
+    }
+    let targets = sort_palette(to, order);
+
+    let mut remapped = vec![targets[0]; from.len()];
+    let last = from.len().saturating_sub(1).max(1);
+    for (rank, index) in ranking(from, order).into_iter().enumerate() {
+        remapped[index] = targets[(rank * (targets.len() - 1) + last / 2) / last];
+    }
+    remapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::random_colors;
+
+    #[test]
+    fn listed_counts_match_the_palettes() {
+        for name in PaletteName::ALL {
+            assert_eq!(name.info().color_count, get_palette(name).len(), "{:?}", name);
+            if let ColorTarget::Grid(depth) = name.target() {
+                assert_eq!(depth.colors(), get_palette(name), "{:?}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in PaletteName::ALL {
+            assert_eq!(PaletteName::from_name(name.id()), Some(name));
+        }
+        assert_eq!(PaletteName::from_name("None"), None);
+    }
+
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+    const GREY: Rgb<u8> = Rgb([128, 128, 128]);
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const YELLOW: Rgb<u8> = Rgb([255, 255, 0]);
+    const GREEN: Rgb<u8> = Rgb([0, 255, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    #[test]
+    fn sorts_by_luminance_and_hue() {
+        let colors = [BLUE, WHITE, GREEN, RED, BLACK, YELLOW, GREY];
+        assert_eq!(sort_palette(&colors, PaletteOrder::Luminance), [BLACK, BLUE, GREY, RED, GREEN, YELLOW, WHITE]);
+        assert_eq!(sort_palette(&colors, PaletteOrder::Hue), [BLACK, GREY, WHITE, RED, YELLOW, GREEN, BLUE]);
+
+        let random = random_colors(64, 0x2545_F491_4F6C_DD1D);
+        let lightness: Vec<f32> = sort_palette(&random, PaletteOrder::Luminance).iter()
+            .map(|&c| srgb_to_oklab(c)[0])
+            .collect();
+        assert!(lightness.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn dedupe_keeps_first_occurrences() {
+        assert_eq!(dedupe_palette(&[RED, BLUE, RED, GREEN, BLUE, RED]), [RED, BLUE, GREEN]);
+        assert!(dedupe_palette(&[]).is_empty());
+    }
+
+    #[test]
+    fn merge_is_the_ordered_union() {
+        let merged = merge_palettes(&[vec![RED, BLUE], vec![BLUE, GREEN, RED], vec![], vec![WHITE, WHITE]]);
+        assert_eq!(merged, [RED, BLUE, GREEN, WHITE]);
+    }
+
+    #[test]
+    fn subset_picks_the_colours_the_frames_use() {
+        let palette = random_colors(16, 0x9E37_79B9_7F4A_7C15);
+        let used = [palette[2], palette[5], palette[9], palette[11], palette[12], palette[14]];
+        let frame = RgbImage::from_fn(30, 20, |x, y| used[((x / 5 + y) % used.len() as u32) as usize]);
+
+        // Exactly the used colours, in palette order
+        let three = RgbImage::from_fn(9, 9, |x, _| used[(x % 3) as usize]);
+        assert_eq!(subset_palette(&palette, &[three], 3, ColorMetric::Oklab), &used[..3]);
+
+        for count in [1, 4, 6] {
+            let subset = subset_palette(&palette, std::slice::from_ref(&frame), count, ColorMetric::Oklab);
+            assert_eq!(subset.len(), count);
+            assert_eq!(dedupe_palette(&subset).len(), count, "{:?}", subset);
+            assert!(subset.iter().all(|c| used.contains(c)), "{}: {:?}", count, subset);
+        }
+        // Only used colours, so fewer than asked for
+        assert_eq!(subset_palette(&palette, &[frame], 10, ColorMetric::Oklab), used);
+        assert_eq!(subset_palette(&palette, &[], 20, ColorMetric::Oklab), palette);
+    }
+
+    #[test]
+    fn remap_follows_the_brightness_order() {
+        let dark = Rgb([10, 0, 40]);
+        let mid = Rgb([90, 20, 20]);
+        let light = Rgb([200, 200, 0]);
+        let remapped = remap_palette(&[WHITE, BLACK, GREY], &[light, dark, mid], PaletteOrder::Luminance);
+        assert_eq!(remapped, [light, dark, mid]);
+
+        // Stretched onto fewer colours, darkest to darkest and lightest to lightest
+        let from = [GREY, BLACK, WHITE, RED, BLUE];
+        let remapped = remap_palette(&from, &[light, dark], PaletteOrder::Luminance);
+        assert_eq!(remapped.len(), from.len());
+        assert_eq!((remapped[1], remapped[2]), (dark, light));
+        assert!(remapped.iter().all(|c| [light, dark].contains(c)));
+
+        assert!(remap_palette(&from, &[], PaletteOrder::Luminance).is_empty());
     }
 }
@@ -0,0 +1,324 @@
+use image::{imageops, Rgb, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::attribute::{AttributeMode, apply_attribute_clash};
+use crate::color::{BitDepth, ColorTarget};
+use crate::dithering::{DitheringConfig, TemporalState, apply_dithering, apply_dithering_temporal};
+use crate::effects::{CRTEffectsConfig, CompositePreset, apply_bezel, apply_composite, apply_crt_effects};
+use crate::palette_file::{PaletteFile, rgb_list};
+use crate::palettes::PaletteName;
+use crate::quantize::{PaletteAlgorithm, extract_palette, pin_colors};
+use crate::vhs::{VhsConfig, apply_vhs};
+
+/// What an automatic palette is built from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaletteScope {
+    #[default]
+    Frame, // every frame gets its own palette
+    Video, // one palette from frames sampled across the whole video
+}
+
+/// Every knob of the frame pipeline. Field names are camelCase on the wire so the
+/// frontend can pass its `ProcessingParams` object straight through.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipelineConfig {
+    pub scale_factor: f32,        // 0.0 to 1.0, size of the low-res frame
+    pub color_count: usize,       // palette size when palette_name is "None"
+    pub palette_algorithm: PaletteAlgorithm, // how automatic palettes are built
+    pub palette_scope: PaletteScope,
+    pub palette_sample_frames: usize, // frames sampled for a PaletteScope::Video palette
+    #[serde(with = "rgb_list")]
+    pub pinned_colors: Vec<Rgb<u8>>, // always part of automatic palettes
+    pub palette_name: String,     // preset name, or "None" for a per-frame palette
+    pub custom_palette: Option<PaletteFile>, // e.g. an imported palette file, overrides palette_name
+    pub palette_id: Option<String>, // user library palette, resolved by PaletteLibrary::resolve
+    pub bit_depth: Option<BitDepth>, // hardware colour space, overrides every palette setting
+    pub grid_colors: usize,       // with bit_depth: colours on screen at once, 0 for the whole grid
+    pub attribute_mode: AttributeMode, // per-cell colour limits of 8-bit hardware
+    pub bezel_image: Option<String>, // image drawn over the output, its alpha showing the screen through
+    #[serde(flatten)]
+    pub dithering: DitheringConfig,
+    #[serde(flatten)]
+    pub vhs: VhsConfig,
+    #[serde(flatten)]
+    pub effects: CRTEffectsConfig,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            scale_factor: 0.5,
+            color_count: 16,
+            palette_algorithm: PaletteAlgorithm::NeuQuant,
+            palette_scope: PaletteScope::Frame,
+            palette_sample_frames: 16,
+            pinned_colors: Vec::new(),
+            palette_name: "None".to_string(),
+            custom_palette: None,
+            palette_id: None,
+            bit_depth: None,
+            grid_colors: 0,
+            attribute_mode: AttributeMode::None,
+            bezel_image: None,
+            dithering: DitheringConfig::default(),
+            vhs: VhsConfig::default(),
+            effects: CRTEffectsConfig::default(),
+        }
+    }
+}
+
+/// Downscale -> quantize -> dither -> upscale -> VHS -> CRT, on plain `RgbImage`s.
+pub struct Pipeline {
+    config: PipelineConfig,
+    // None means the palette is extracted from each frame
+    target: Option<ColorTarget>,
+    bezel: Option<RgbaImage>,
+    // The bezel at the last output size
+    scaled_bezel: Mutex<Option<Arc<RgbaImage>>>,
+}
+
+impl Pipeline {
+    pub fn new(config: PipelineConfig) -> Result<Self, String> {
+        if let Some(id) = &config.palette_id {
+            return Err(format!("Palette {} is in the palette library, which isn't available here", id));
+        }
+
+        let target = if let Some(depth) = config.bit_depth {
+            // Reduced to grid_colors per frame otherwise
+            (config.grid_colors == 0).then_some(ColorTarget::Grid(depth))
+        } else if let Some(custom) = &config.custom_palette {
+            Some(ColorTarget::Palette(custom.colors.clone()))
+        } else if config.palette_name == "None" {
+            None
+        } else {
+            let name = PaletteName::from_name(&config.palette_name)
+                .ok_or_else(|| {
+                    let known: Vec<&str> = PaletteName::ALL.iter().map(|p| p.id()).collect();
+                    format!("Unknown palette: {} (expected None or one of {})", config.palette_name, known.join(", "))
+                })?;
+            Some(name.target())
+        };
+
+        let bezel = config.bezel_image.as_deref()
+            .map(|path| {
+                image::open(path)
+                    .map(|img| img.to_rgba8())
+                    .map_err(|e| format!("Failed to load bezel image {}: {}", path, e))
+            })
+            .transpose()?;
+
+        Ok(Self { config, target, bezel, scaled_bezel: Mutex::new(None) })
+    }
+
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// The fixed palette, or None when each frame gets its own or colours
+    /// snap to a bit-depth grid.
+    pub fn palette(&self) -> Option<&[Rgb<u8>]> {
+        match &self.target {
+            Some(ColorTarget::Palette(palette)) => Some(palette),
+            _ => None,
+        }
+    }
+
+    /// Every colour the output can use, for exporting: the fixed palette or all
+    /// of a grid's colours. None when each frame gets its own palette.
+    pub fn export_colors(&self) -> Option<Vec<Rgb<u8>>> {
+        match self.target.as_ref()? {
+            ColorTarget::Palette(palette) => Some(palette.clone()),
+            ColorTarget::Grid(depth) => Some(depth.colors()),
+        }
+    }
+
+    /// True when the settings ask for one automatic palette over the whole
+    /// video, which `use_sampled_palette` has yet to provide.
+    pub fn needs_sampled_palette(&self) -> bool {
+        self.target.is_none() && self.config.palette_scope == PaletteScope::Video
+    }
+
+    /// Fixes the automatic palette to one built from `frames`, low-resolution
+    /// frames sampled across the video (see `video::sample_frames`).
+    pub fn use_sampled_palette(&mut self, frames: &[RgbImage]) {
+        self.use_palette(self.auto_palette(frames));
+    }
+
+    /// Fixes the automatic palette to `palette`, e.g. one `use_sampled_palette`
+    /// built earlier for the same video.
+    pub fn use_palette(&mut self, palette: Vec<Rgb<u8>>) {
+        self.target = Some(ColorTarget::Palette(palette));
+    }
+
+    /// Runs the whole pipeline, returning an image the same size as the input.
+    pub fn process(&self, img: &RgbImage) -> RgbImage {
+        let (width, height) = img.dimensions();
+        self.process_to(img, width, height)
+    }
+
+    /// Runs the whole pipeline, upscaling the result to `width` x `height`.
+    pub fn process_to(&self, img: &RgbImage, width: u32, height: u32) -> RgbImage {
+        let small = self.downscale(img);
+        let small = self.quantize(&small);
+        self.finish(&small, width, height, 0)
+    }
+
+    /// Same as `process`, for consecutive video frames: dither decisions are
+    /// carried in `state` according to `temporal_threshold`, and `frame` is
+    /// the frame's index for effects that change over time.
+    pub fn process_temporal(&self, img: &RgbImage, state: &mut TemporalState, frame: u64) -> RgbImage {
+        let (width, height) = img.dimensions();
+        let small = self.downscale(img);
+        let small = self.quantize_temporal(&small, state);
+        self.finish(&small, width, height, frame)
+    }
+
+    /// Size of the low-resolution frame for a `width` x `height` source.
+    pub fn scaled_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            (width as f32 * self.config.scale_factor).max(1.0) as u32,
+            (height as f32 * self.config.scale_factor).max(1.0) as u32,
+        )
+    }
+
+    /// Nearest-neighbour downscale by `scale_factor`.
+    pub fn downscale(&self, img: &RgbImage) -> RgbImage {
+        let (width, height) = img.dimensions();
+        let (scaled_width, scaled_height) = self.scaled_dimensions(width, height);
+        imageops::resize(img, scaled_width, scaled_height, imageops::FilterType::Nearest)
+    }
+
+    /// Colour quantization & dithering on the low-resolution frame.
+    pub fn quantize(&self, img: &RgbImage) -> RgbImage {
+        self.quantize_with(img, None)
+    }
+
+    /// Colour quantization & dithering of one frame in a sequence.
+    pub fn quantize_temporal(&self, img: &RgbImage, state: &mut TemporalState) -> RgbImage {
+        self.quantize_with(img, Some(state))
+    }
+
+    fn quantize_with(&self, img: &RgbImage, state: Option<&mut TemporalState>) -> RgbImage {
+        let extracted;
+        let target = match &self.target {
+            Some(target) => target,
+            None => {
+                extracted = ColorTarget::Palette(self.auto_palette(std::slice::from_ref(img)));
+                &extracted
+            }
+        };
+
+        if matches!(target, ColorTarget::Palette(palette) if palette.is_empty()) {
+            return img.clone();
+        }
+        if self.config.attribute_mode != AttributeMode::None {
+            // Cells pick their colours afresh every frame, so earlier dither
+            // decisions may not be allowed any more
+            if let Some(state) = state {
+                state.reset();
+            }
+            return apply_attribute_clash(img, target, self.config.attribute_mode, &self.config.dithering);
+        }
+        match state {
+            Some(state) => apply_dithering_temporal(img, target, &self.config.dithering, state),
+            None => apply_dithering(img, target, &self.config.dithering),
+        }
+    }
+
+    // Palette built from `frames` for automatic palettes: `color_count`
+    // colours, or `grid_colors` colours of the bit-depth grid, including the
+    // pinned colours
+    fn auto_palette(&self, frames: &[RgbImage]) -> Vec<Rgb<u8>> {
+        let count = match self.config.bit_depth {
+            Some(_) => self.config.grid_colors,
+            None => self.config.color_count,
+        };
+        // Room for the pinned colours is made after extraction, so that they
+        // replace the extracted colours they'd duplicate
+        let palette = extract_palette(frames, count, self.config.palette_algorithm);
+        let palette = pin_colors(palette, &self.config.pinned_colors, count);
+        match self.config.bit_depth {
+            Some(depth) => grid_palette(palette, depth),
+            None => palette,
+        }
+    }
+
+    /// Composite signal, nearest-neighbour upscale to the output size, then
+    /// tape, CRT effects and the bezel. `frame` is the index of `img` in its
+    /// video.
+    pub fn finish(&self, img: &RgbImage, width: u32, height: u32, frame: u64) -> RgbImage {
+        let decoded;
+        let img = match self.config.effects.composite {
+            CompositePreset::None => img,
+            preset => {
+                decoded = apply_composite(img, preset, frame);
+                &decoded
+            }
+        };
+        let upscaled_img = imageops::resize(img, width, height, imageops::FilterType::Nearest);
+        let upscaled_img = apply_vhs(&upscaled_img, &self.config.vhs, frame);
+        let mut output = apply_crt_effects(&upscaled_img, img.height(), self.config.effects);
+        if let Some(bezel) = self.bezel_for(width, height) {
+            apply_bezel(&mut output, &bezel);
+        }
+        output
+    }
+
+    // The bezel stretched to the output size, resized only when that changes
+    fn bezel_for(&self, width: u32, height: u32) -> Option<Arc<RgbaImage>> {
+        let bezel = self.bezel.as_ref()?;
+        let mut scaled = self.scaled_bezel.lock().unwrap_or_else(|e| e.into_inner());
+        match scaled.as_ref() {
+            Some(cached) if cached.dimensions() == (width, height) => Some(cached.clone()),
+            _ => {
+                let resized = Arc::new(imageops::resize(bezel, width, height, imageops::FilterType::Triangle));
+                *scaled = Some(resized.clone());
+                Some(resized)
+            }
+        }
+    }
+}
+
+// The colours of `palette` moved onto the bit-depth grid. Entries that land on
+// the same grid colour are merged, so there may be fewer.
+fn grid_palette(palette: Vec<Rgb<u8>>, depth: BitDepth) -> Vec<Rgb<u8>> {
+    let mut snapped: Vec<Rgb<u8>> = Vec::with_capacity(palette.len());
+    for color in palette {
+        let color = depth.snap(color);
+        if !snapped.contains(&color) {
+            snapped.push(color);
+        }
+    }
+    snapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette_file::PaletteFormat;
+    use crate::palettes::get_palette;
+
+    #[test]
+    fn every_preset_can_be_exported() {
+        for name in PaletteName::ALL {
+            let config = PipelineConfig { palette_name: name.id().to_string(), ..Default::default() };
+            let colors = Pipeline::new(config).unwrap().export_colors();
+            assert_eq!(colors.as_deref(), Some(get_palette(name).as_slice()), "{:?}", name);
+
+            let file = PaletteFile { name: name.id().to_string(), colors: colors.unwrap() };
+            assert!(file.encode(PaletteFormat::Gpl).is_ok(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn only_automatic_palettes_have_nothing_to_export() {
+        let automatic = PipelineConfig { palette_name: "None".to_string(), ..Default::default() };
+        assert_eq!(Pipeline::new(automatic).unwrap().export_colors(), None);
+
+        let depth = BitDepth::uniform(2);
+        let grid = PipelineConfig { bit_depth: Some(depth), ..Default::default() };
+        assert_eq!(Pipeline::new(grid).unwrap().export_colors(), Some(depth.colors()));
+    }
+}
@@ -0,0 +1,691 @@
+use color_quant::NeuQuant;
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::color::{ColorMetric, PaletteMatcher, oklab_to_srgb, srgb_to_oklab};
+
+// Upper bound on k-means rounds; most palettes settle well before this
+const KMEANS_ROUNDS: usize = 16;
+
+// Wu's histogram keeps 5 bits per channel, with a zero plane in front of each
+// axis for the cumulative moments
+const WU_SIDE: usize = 33;
+
+/// How the automatic palette is built.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaletteAlgorithm {
+    #[default]
+    NeuQuant,  // Kohonen neural network (color_quant), sample factor 10
+    MedianCut, // repeatedly splits the colour box with the widest weighted spread
+    KMeans,    // k-means in OKLab, seeded with median cut
+    Octree,    // merges the least-used octree leaves
+    Wu,        // Xiaolin Wu's variance-minimizing cuts
+}
+
+/// Up to `count` colours that best represent all of `frames` together, so a
+/// palette can cover a single frame or a sample of a whole video. Frames with
+/// no more than `count` distinct colours get exactly those, most used first.
+pub fn extract_palette(frames: &[RgbImage], count: usize, algorithm: PaletteAlgorithm) -> Vec<Rgb<u8>> {
+    if count == 0 || frames.iter().all(|f| f.as_raw().is_empty()) {
+        return Vec::new();
+    }
+
+    let mut colors = histogram(frames);
+    if colors.len() <= count {
+        // Stable, so equally used colours stay in colour order
+        colors.sort_by_key(|&(_, n)| Reverse(n));
+        return colors.into_iter().map(|(color, _)| Rgb(color)).collect();
+    }
+
+    match algorithm {
+        PaletteAlgorithm::NeuQuant => neuquant(frames, count),
+        PaletteAlgorithm::MedianCut => median_cut(&colors, count),
+        PaletteAlgorithm::KMeans => kmeans(&colors, count),
+        PaletteAlgorithm::Octree => octree(&colors, count),
+        PaletteAlgorithm::Wu => wu(frames, count),
+    }
+}
+
+/// `palette` with the `pinned` colours added, keeping it at `count` colours
+/// when there's room: each pinned colour takes the place of the extracted
+/// colour closest to it (in OKLab), which it would mostly duplicate. Pinned
+/// colours come first; beyond `count` of them, the rest are dropped.
+pub fn pin_colors(palette: Vec<Rgb<u8>>, pinned: &[Rgb<u8>], count: usize) -> Vec<Rgb<u8>> {
+    let mut colors: Vec<Rgb<u8>> = Vec::with_capacity(count);
+    for &color in pinned {
+        if !colors.contains(&color) && colors.len() < count {
+            colors.push(color);
+        }
+    }
+    let mut free: Vec<Rgb<u8>> = Vec::with_capacity(palette.len());
+    for color in palette {
+        if !colors.contains(&color) && !free.contains(&color) {
+            free.push(color);
+        }
+    }
+
+    let distance = |a: [f32; 3], b: [f32; 3]| (0..3).map(|k| (a[k] - b[k]) * (a[k] - b[k])).sum::<f32>();
+    for &color in &colors {
+        if colors.len() + free.len() <= count || free.is_empty() {
+            break;
+        }
+        let point = srgb_to_oklab(color);
+        let closest = (0..free.len())
+            .min_by(|&a, &b| {
+                distance(point, srgb_to_oklab(free[a])).total_cmp(&distance(point, srgb_to_oklab(free[b])))
+            })
+            .expect("free colours left");
+        free.remove(closest);
+    }
+    // More pinned colours than extracted ones were dropped for
+    free.truncate(count.saturating_sub(colors.len()));
+
+    colors.extend(free);
+    colors
+}
+
+/// A palette colour with the share of pixels that map to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorUsage {
+    pub color: [u8; 3],
+    pub percent: f32, // 0.0 to 100.0
+}
+
+/// How much of `frames` each palette colour covers when every pixel takes its
+/// closest colour, most used first.
+pub fn palette_usage(frames: &[RgbImage], palette: &[Rgb<u8>], metric: ColorMetric) -> Vec<ColorUsage> {
+    let matcher = PaletteMatcher::new(palette, metric);
+    let mut counts: HashMap<Rgb<u8>, u64> = HashMap::new();
+    let mut total = 0;
+    for (color, n) in histogram(frames) {
+        *counts.entry(matcher.nearest(Rgb(color))).or_default() += n;
+        total += n;
+    }
+
+    let mut usage: Vec<ColorUsage> = Vec::with_capacity(palette.len());
+    for &color in palette {
+        if usage.iter().any(|u| u.color == color.0) {
+            continue;
+        }
+        let n = counts.get(&color).copied().unwrap_or(0);
+        usage.push(ColorUsage { color: color.0, percent: (n as f64 * 100.0 / total.max(1) as f64) as f32 });
+    }
+    // Stable, so equally used colours keep their palette order
+    usage.sort_by(|a, b| b.percent.total_cmp(&a.percent));
+    usage
+}
+
+// Distinct colours with their pixel counts, sorted by colour so results don't
+// depend on hash order
+fn histogram(frames: &[RgbImage]) -> Vec<([u8; 3], u64)> {
+    let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+    for frame in frames {
+        for pixel in frame.pixels() {
+            *counts.entry(pixel.0).or_default() += 1;
+        }
+    }
+    let mut colors: Vec<([u8; 3], u64)> = counts.into_iter().collect();
+    colors.sort_unstable_by_key(|&(color, _)| color);
+    colors
+}
+
+fn neuquant(frames: &[RgbImage], count: usize) -> Vec<Rgb<u8>> {
+    // NeuQuant expects RGBA, 4 bytes per pixel
+    let pixels: usize = frames.iter().map(|f| f.as_raw().len() / 3).sum();
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    for frame in frames {
+        for c in frame.as_raw().chunks_exact(3) {
+            rgba.extend_from_slice(&[c[0], c[1], c[2], 255]);
+        }
+    }
+
+    let nq = NeuQuant::new(10, count, &rgba);
+    // color_map is [r, g, b, a, r, g, b, a, ...]
+    nq.color_map_rgba()
+        .chunks(4)
+        .map(|c| Rgb([c[0], c[1], c[2]]))
+        .collect()
+}
+
+// Pixel-weighted mean of some histogram entries
+fn mean_color(colors: &[([u8; 3], u64)]) -> Rgb<u8> {
+    let mut sum = [0u64; 3];
+    let mut weight = 0u64;
+    for &(color, count) in colors {
+        for c in 0..3 {
+            sum[c] += color[c] as u64 * count;
+        }
+        weight += count;
+    }
+    Rgb(sum.map(|s| ((s + weight / 2) / weight.max(1)) as u8))
+}
+
+fn median_cut(colors: &[([u8; 3], u64)], count: usize) -> Vec<Rgb<u8>> {
+    let mut boxes: Vec<Vec<([u8; 3], u64)>> = vec![colors.to_vec()];
+
+    while boxes.len() < count {
+        // The box whose widest channel, weighted by its pixels, spans the most
+        let widest = boxes.iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (axis, range) = widest_axis(b);
+                let pixels: u64 = b.iter().map(|&(_, n)| n).sum();
+                (i, axis, range as u64 * pixels)
+            })
+            .max_by_key(|&(i, _, score)| (score, Reverse(i)));
+        let Some((index, axis, _)) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|&(color, _)| (color[axis], color));
+        let total: u64 = colors.iter().map(|&(_, n)| n).sum();
+        let mut below = 0;
+        let mut split = colors.len() - 1;
+        for (i, &(_, n)) in colors.iter().enumerate() {
+            below += n;
+            if below * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let upper = colors.split_off(split.clamp(1, colors.len() - 1));
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|b| mean_color(b)).collect()
+}
+
+// (channel, max - min) of the channel with the largest spread
+fn widest_axis(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|axis| {
+            let (min, max) = colors.iter().fold((255, 0), |(min, max), &(c, _)| (c[axis].min(min), c[axis].max(max)));
+            (axis, max - min)
+        })
+        .max_by_key(|&(axis, range)| (range, Reverse(axis)))
+        .expect("three channels")
+}
+
+fn kmeans(colors: &[([u8; 3], u64)], count: usize) -> Vec<Rgb<u8>> {
+    let points: Vec<([f32; 3], f32)> = colors.iter()
+        .map(|&(color, n)| (srgb_to_oklab(Rgb(color)), n as f32))
+        .collect();
+    let mut centroids: Vec<[f32; 3]> = median_cut(colors, count).into_iter()
+        .map(srgb_to_oklab)
+        .collect();
+
+    let nearest = |point: &[f32; 3], centroids: &[[f32; 3]]| {
+        let mut best = (f32::MAX, 0);
+        for (i, c) in centroids.iter().enumerate() {
+            let d = (0..3).map(|k| (point[k] - c[k]) * (point[k] - c[k])).sum::<f32>();
+            if d < best.0 {
+                best = (d, i);
+            }
+        }
+        best.1
+    };
+
+    let mut assignment: Vec<usize> = Vec::new();
+    for _ in 0..KMEANS_ROUNDS {
+        let next: Vec<usize> = points.par_iter().map(|(p, _)| nearest(p, &centroids)).collect();
+        if next == assignment {
+            break;
+        }
+        assignment = next;
+
+        let mut sums = vec![([0f64; 3], 0f64); centroids.len()];
+        for ((point, weight), &cluster) in points.iter().zip(&assignment) {
+            for (sum, value) in sums[cluster].0.iter_mut().zip(point) {
+                *sum += (value * weight) as f64;
+            }
+            sums[cluster].1 += *weight as f64;
+        }
+        // An emptied cluster keeps its old centroid
+        for (centroid, (sum, weight)) in centroids.iter_mut().zip(&sums) {
+            if *weight > 0.0 {
+                *centroid = sum.map(|s| (s / weight) as f32);
+            }
+        }
+    }
+
+    let mut palette: Vec<Rgb<u8>> = Vec::with_capacity(centroids.len());
+    for centroid in centroids {
+        let color = oklab_to_srgb(centroid);
+        if !palette.contains(&color) {
+            palette.push(color);
+        }
+    }
+    palette
+}
+
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    depth: u32,
+    parent: Option<usize>,
+    sum: [u64; 3],
+    pixels: u64,
+    leaf: bool,
+}
+
+// Builds the full 8-level octree of the histogram, then folds the children of
+// the deepest, least-used nodes into their parent until at most `count` leaves
+// remain. Each leaf is one palette colour, the mean of the pixels under it.
+fn octree(colors: &[([u8; 3], u64)], count: usize) -> Vec<Rgb<u8>> {
+    let mut nodes = vec![OctreeNode { children: [None; 8], depth: 0, parent: None, sum: [0; 3], pixels: 0, leaf: false }];
+
+    for &(color, n) in colors {
+        let mut node = 0;
+        for depth in 0..8 {
+            let bit = 7 - depth;
+            let child = (((color[0] >> bit) & 1) << 2 | ((color[1] >> bit) & 1) << 1 | ((color[2] >> bit) & 1)) as usize;
+            node = match nodes[node].children[child] {
+                Some(existing) => existing,
+                None => {
+                    nodes.push(OctreeNode {
+                        children: [None; 8],
+                        depth: depth + 1,
+                        parent: Some(node),
+                        sum: [0; 3],
+                        pixels: 0,
+                        leaf: depth == 7,
+                    });
+                    let id = nodes.len() - 1;
+                    nodes[node].children[child] = Some(id);
+                    id
+                }
+            };
+        }
+        for (sum, value) in nodes[node].sum.iter_mut().zip(color) {
+            *sum += value as u64 * n;
+        }
+        nodes[node].pixels += n;
+    }
+
+    // Totals of the inner nodes, deepest first since children follow parents
+    for id in (1..nodes.len()).rev() {
+        let parent = nodes[id].parent.expect("only the root has no parent");
+        let (sum, pixels) = (nodes[id].sum, nodes[id].pixels);
+        for (total, value) in nodes[parent].sum.iter_mut().zip(sum) {
+            *total += value;
+        }
+        nodes[parent].pixels += pixels;
+    }
+
+    let all_leaves = |nodes: &[OctreeNode], id: usize| {
+        nodes[id].children.iter().flatten().all(|&child| nodes[child].leaf)
+    };
+    let mut leaves = colors.len();
+    // Deepest first, then fewest pixels
+    let mut reducible: BinaryHeap<(u32, Reverse<u64>, Reverse<usize>)> = (0..nodes.len())
+        .filter(|&id| !nodes[id].leaf && all_leaves(&nodes, id))
+        .map(|id| (nodes[id].depth, Reverse(nodes[id].pixels), Reverse(id)))
+        .collect();
+
+    // Nodes whose folding would leave fewer than `count` colours, folded only
+    // when nothing else is left
+    let mut deferred: Vec<usize> = Vec::new();
+
+    while leaves > count {
+        let id = match reducible.pop() {
+            Some((_, _, Reverse(id))) => {
+                let children = nodes[id].children.iter().flatten().count();
+                if leaves - (children - 1) < count {
+                    deferred.push(id);
+                    continue;
+                }
+                id
+            }
+            None => {
+                // Fewest children, so the palette comes out as close to `count` as it can
+                let Some(index) = (0..deferred.len())
+                    .min_by_key(|&i| (nodes[deferred[i]].children.iter().flatten().count(), Reverse(nodes[deferred[i]].depth)))
+                else {
+                    break;
+                };
+                deferred.swap_remove(index)
+            }
+        };
+        let children = nodes[id].children.iter().flatten().count();
+        nodes[id].leaf = true;
+        nodes[id].children = [None; 8];
+        leaves -= children - 1;
+
+        if let Some(parent) = nodes[id].parent {
+            if all_leaves(&nodes, parent) {
+                reducible.push((nodes[parent].depth, Reverse(nodes[parent].pixels), Reverse(parent)));
+            }
+        }
+    }
+
+    // Reachable leaves, in tree order
+    let mut palette = Vec::with_capacity(leaves);
+    let mut stack = vec![0];
+    while let Some(id) = stack.pop() {
+        let node = &nodes[id];
+        if node.leaf {
+            palette.push(Rgb(node.sum.map(|s| ((s + node.pixels / 2) / node.pixels.max(1)) as u8)));
+        } else {
+            stack.extend(node.children.iter().rev().flatten());
+        }
+    }
+    palette
+}
+
+// Cumulative colour moments over Wu's 33x33x33 histogram: pixel count, sum of
+// each channel and sum of squared magnitudes of every box from the origin
+struct Moments {
+    weight: Vec<i64>,
+    red: Vec<i64>,
+    green: Vec<i64>,
+    blue: Vec<i64>,
+    squares: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct WuBox {
+    // Lower bounds are exclusive, upper bounds inclusive
+    r0: usize,
+    r1: usize,
+    g0: usize,
+    g1: usize,
+    b0: usize,
+    b1: usize,
+    volume: usize,
+}
+
+fn wu_index(r: usize, g: usize, b: usize) -> usize {
+    (r * WU_SIDE + g) * WU_SIDE + b
+}
+
+impl Moments {
+    fn new(frames: &[RgbImage]) -> Self {
+        let cells = WU_SIDE * WU_SIDE * WU_SIDE;
+        let mut m = Moments {
+            weight: vec![0; cells],
+            red: vec![0; cells],
+            green: vec![0; cells],
+            blue: vec![0; cells],
+            squares: vec![0.0; cells],
+        };
+
+        for frame in frames {
+            for pixel in frame.pixels() {
+                let [r, g, b] = pixel.0;
+                let index = wu_index((r >> 3) as usize + 1, (g >> 3) as usize + 1, (b >> 3) as usize + 1);
+                m.weight[index] += 1;
+                m.red[index] += r as i64;
+                m.green[index] += g as i64;
+                m.blue[index] += b as i64;
+                m.squares[index] += (r as f64).powi(2) + (g as f64).powi(2) + (b as f64).powi(2);
+            }
+        }
+
+        // Turn the histogram into moments of the boxes from the origin
+        for r in 1..WU_SIDE {
+            let mut area = [[0i64; WU_SIDE]; 4];
+            let mut area_squares = [0f64; WU_SIDE];
+            for g in 1..WU_SIDE {
+                let mut line = [0i64; 4];
+                let mut line_squares = 0f64;
+                for b in 1..WU_SIDE {
+                    let index = wu_index(r, g, b);
+                    let previous = wu_index(r - 1, g, b);
+                    for (k, moment) in [&mut m.weight, &mut m.red, &mut m.green, &mut m.blue].into_iter().enumerate() {
+                        line[k] += moment[index];
+                        area[k][b] += line[k];
+                        moment[index] = moment[previous] + area[k][b];
+                    }
+                    line_squares += m.squares[index];
+                    area_squares[b] += line_squares;
+                    m.squares[index] = m.squares[previous] + area_squares[b];
+                }
+            }
+        }
+        m
+    }
+}
+
+// Sum of a cumulative moment over `cube`
+fn volume<T>(cube: &WuBox, m: &[T]) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+{
+    m[wu_index(cube.r1, cube.g1, cube.b1)] - m[wu_index(cube.r1, cube.g1, cube.b0)]
+        - m[wu_index(cube.r1, cube.g0, cube.b1)] + m[wu_index(cube.r1, cube.g0, cube.b0)]
+        - m[wu_index(cube.r0, cube.g1, cube.b1)] + m[wu_index(cube.r0, cube.g1, cube.b0)]
+        + m[wu_index(cube.r0, cube.g0, cube.b1)] - m[wu_index(cube.r0, cube.g0, cube.b0)]
+}
+
+// The part of `volume` that doesn't depend on the upper bound along `axis`
+fn bottom(cube: &WuBox, axis: usize, m: &[i64]) -> i64 {
+    match axis {
+        0 => -m[wu_index(cube.r0, cube.g1, cube.b1)] + m[wu_index(cube.r0, cube.g1, cube.b0)]
+            + m[wu_index(cube.r0, cube.g0, cube.b1)] - m[wu_index(cube.r0, cube.g0, cube.b0)],
+        1 => -m[wu_index(cube.r1, cube.g0, cube.b1)] + m[wu_index(cube.r1, cube.g0, cube.b0)]
+            + m[wu_index(cube.r0, cube.g0, cube.b1)] - m[wu_index(cube.r0, cube.g0, cube.b0)],
+        _ => -m[wu_index(cube.r1, cube.g1, cube.b0)] + m[wu_index(cube.r1, cube.g0, cube.b0)]
+            + m[wu_index(cube.r0, cube.g1, cube.b0)] - m[wu_index(cube.r0, cube.g0, cube.b0)],
+    }
+}
+
+// The rest of `volume` with the upper bound along `axis` moved to `position`
+fn top(cube: &WuBox, axis: usize, position: usize, m: &[i64]) -> i64 {
+    match axis {
+        0 => m[wu_index(position, cube.g1, cube.b1)] - m[wu_index(position, cube.g1, cube.b0)]
+            - m[wu_index(position, cube.g0, cube.b1)] + m[wu_index(position, cube.g0, cube.b0)],
+        1 => m[wu_index(cube.r1, position, cube.b1)] - m[wu_index(cube.r1, position, cube.b0)]
+            - m[wu_index(cube.r0, position, cube.b1)] + m[wu_index(cube.r0, position, cube.b0)],
+        _ => m[wu_index(cube.r1, cube.g1, position)] - m[wu_index(cube.r1, cube.g0, position)]
+            - m[wu_index(cube.r0, cube.g1, position)] + m[wu_index(cube.r0, cube.g0, position)],
+    }
+}
+
+// Sum of squared distances from the box's mean colour, weighted by pixels
+fn variance(cube: &WuBox, m: &Moments) -> f64 {
+    let weight = volume(cube, &m.weight);
+    if weight == 0 {
+        return 0.0;
+    }
+    let [r, g, b] = [&m.red, &m.green, &m.blue].map(|moment| volume(cube, moment) as f64);
+    volume(cube, &m.squares) - (r * r + g * g + b * b) / weight as f64
+}
+
+// Best place to cut `cube` along `axis`, as (score, position); the score is
+// the between-halves variance to maximize
+fn maximize(cube: &WuBox, axis: usize, first: usize, last: usize, whole: [i64; 4], m: &Moments) -> (f64, Option<usize>) {
+    let moments = [&m.red, &m.green, &m.blue, &m.weight];
+    let base = moments.map(|moment| bottom(cube, axis, moment));
+    let mut best = (0.0, None);
+
+    for position in first..last {
+        let lower: [i64; 4] = std::array::from_fn(|k| base[k] + top(cube, axis, position, moments[k]));
+        let upper: [i64; 4] = std::array::from_fn(|k| whole[k] - lower[k]);
+        if lower[3] == 0 || upper[3] == 0 {
+            continue;
+        }
+        let score = [lower, upper].iter()
+            .map(|half| (half[0] as f64).powi(2) + (half[1] as f64).powi(2) + (half[2] as f64).powi(2))
+            .zip([lower[3], upper[3]])
+            .map(|(magnitude, weight)| magnitude / weight as f64)
+            .sum::<f64>();
+        if score > best.0 {
+            best = (score, Some(position));
+        }
+    }
+    best
+}
+
+// Splits `cube` in two along the axis and position that best reduce variance;
+// `cube` keeps the lower half and the upper half is returned
+fn cut(cube: &mut WuBox, m: &Moments) -> Option<WuBox> {
+    let whole = [&m.red, &m.green, &m.blue, &m.weight].map(|moment| volume(cube, moment));
+    let bounds = [(cube.r0, cube.r1), (cube.g0, cube.g1), (cube.b0, cube.b1)];
+    let (_, axis, position) = (0..3)
+        .map(|axis| {
+            let (score, position) = maximize(cube, axis, bounds[axis].0 + 1, bounds[axis].1, whole, m);
+            (score, axis, position)
+        })
+        .fold((f64::MIN, 0, None), |best, next| if next.0 > best.0 { next } else { best });
+    let position = position?;
+
+    let mut upper = *cube;
+    match axis {
+        0 => {
+            upper.r0 = position;
+            cube.r1 = position;
+        }
+        1 => {
+            upper.g0 = position;
+            cube.g1 = position;
+        }
+        _ => {
+            upper.b0 = position;
+            cube.b1 = position;
+        }
+    }
+    for b in [&mut *cube, &mut upper] {
+        b.volume = (b.r1 - b.r0) * (b.g1 - b.g0) * (b.b1 - b.b0);
+    }
+    Some(upper)
+}
+
+fn wu(frames: &[RgbImage], count: usize) -> Vec<Rgb<u8>> {
+    let m = Moments::new(frames);
+    let side = WU_SIDE - 1;
+    let mut boxes = vec![WuBox { r0: 0, r1: side, g0: 0, g1: side, b0: 0, b1: side, volume: side * side * side }];
+    let mut variances = vec![0.0];
+    let mut next = 0;
+
+    while boxes.len() < count {
+        match cut(&mut boxes[next], &m) {
+            Some(upper) => {
+                let score = |b: &WuBox| if b.volume > 1 { variance(b, &m) } else { 0.0 };
+                variances[next] = score(&boxes[next]);
+                variances.push(score(&upper));
+                boxes.push(upper);
+            }
+            // Can't be split any further
+            None => variances[next] = 0.0,
+        }
+
+        let (most, &worst) = variances.iter()
+            .enumerate()
+            .fold((0, &variances[0]), |best, next| if next.1 > best.1 { next } else { best });
+        if worst <= 0.0 {
+            break;
+        }
+        next = most;
+    }
+
+    boxes.iter()
+        .filter_map(|cube| {
+            let weight = volume(cube, &m.weight);
+            (weight > 0).then(|| {
+                let sums = [&m.red, &m.green, &m.blue].map(|moment| volume(cube, moment));
+                Rgb(sums.map(|s| ((s + weight / 2) / weight) as u8))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::collections::HashSet;
+
+    const ALGORITHMS: [PaletteAlgorithm; 5] = [
+        PaletteAlgorithm::NeuQuant,
+        PaletteAlgorithm::MedianCut,
+        PaletteAlgorithm::KMeans,
+        PaletteAlgorithm::Octree,
+        PaletteAlgorithm::Wu,
+    ];
+
+    fn gradient() -> RgbImage {
+        test_util::gradient(64, 48)
+    }
+
+    fn distinct(colors: &[Rgb<u8>]) -> usize {
+        colors.iter().collect::<HashSet<_>>().len()
+    }
+
+    #[test]
+    fn palettes_stay_within_count() {
+        let frames = [gradient()];
+        for algorithm in ALGORITHMS {
+            for count in [1, 2, 16, 256] {
+                let palette = extract_palette(&frames, count, algorithm);
+                assert!(!palette.is_empty(), "{:?}, {}", algorithm, count);
+                assert!(palette.len() <= count, "{:?}: {} colours for {}", algorithm, palette.len(), count);
+            }
+        }
+    }
+
+    #[test]
+    fn few_colours_are_kept() {
+        let single = [RgbImage::from_pixel(16, 16, Rgb([200, 30, 90]))];
+        let two = [RgbImage::from_fn(16, 16, |x, _| if x < 8 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) })];
+        for algorithm in ALGORITHMS {
+            let palette = extract_palette(&single, 16, algorithm);
+            assert_eq!(palette, [Rgb([200, 30, 90])], "{:?}", algorithm);
+
+            // More colours asked for than the image has
+            let palette = extract_palette(&two, 16, algorithm);
+            assert_eq!(palette, [Rgb([0, 0, 0]), Rgb([255, 255, 255])], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn empty_input_gives_an_empty_palette() {
+        for algorithm in ALGORITHMS {
+            assert!(extract_palette(&[], 16, algorithm).is_empty());
+            assert!(extract_palette(&[RgbImage::new(0, 0)], 16, algorithm).is_empty());
+            assert!(extract_palette(&[gradient()], 0, algorithm).is_empty());
+        }
+    }
+
+    #[test]
+    fn pinned_colours_are_kept() {
+        let palette = extract_palette(&[gradient()], 8, PaletteAlgorithm::MedianCut);
+        let pinned = [Rgb([0, 0, 0]), Rgb([255, 0, 255]), Rgb([0, 0, 0]), palette[0]];
+        for count in [2, 3, 8, 12] {
+            let pinned_colors = pin_colors(palette.clone(), &pinned, count);
+            assert!(pinned_colors.len() <= count, "{}: {:?}", count, pinned_colors);
+            assert_eq!(distinct(&pinned_colors), pinned_colors.len(), "{:?}", pinned_colors);
+            // Past `count`, the first pinned colours win
+            let unique_pinned: Vec<_> = [pinned[0], pinned[1], pinned[3]].into_iter().take(count).collect();
+            assert!(unique_pinned.iter().all(|c| pinned_colors.contains(c)), "{}: {:?}", count, pinned_colors);
+        }
+        assert_eq!(pin_colors(palette.clone(), &[], 8), palette);
+    }
+
+    #[test]
+    fn usage_adds_up_and_merges_duplicates() {
+        let (red, green, blue) = (Rgb([255, 0, 0]), Rgb([0, 255, 0]), Rgb([0, 0, 255]));
+        let frame = RgbImage::from_fn(10, 10, |x, _| if x < 7 { Rgb([250, 10, 5]) } else { blue });
+        let usage = palette_usage(&[frame], &[blue, red, green, red], ColorMetric::Rgb);
+        let found: Vec<([u8; 3], f32)> = usage.iter().map(|u| (u.color, u.percent)).collect();
+        assert_eq!(found, [(red.0, 70.0), (blue.0, 30.0), (green.0, 0.0)]);
+
+        let palette = [red, green, blue, Rgb([0, 0, 0]), Rgb([255, 255, 255]), green];
+        let frames = [gradient(), RgbImage::from_pixel(8, 8, Rgb([20, 20, 20]))];
+        for metric in [ColorMetric::Rgb, ColorMetric::Oklab] {
+            let usage = palette_usage(&frames, &palette, metric);
+            assert_eq!(usage.len(), 5, "{:?}", metric);
+            let total: f32 = usage.iter().map(|u| u.percent).sum();
+            assert!((total - 100.0).abs() < 1e-3, "{:?}: {}", metric, total);
+            assert!(usage.windows(2).all(|w| w[0].percent >= w[1].percent), "{:?}", metric);
+        }
+
+        // Nothing to cover
+        assert!(palette_usage(&[], &palette, ColorMetric::Rgb).iter().all(|u| u.percent == 0.0));
+    }
+}
@@ -0,0 +1,38 @@
+// Fixtures shared by the unit tests
+
+use image::{Rgb, RgbImage};
+
+use crate::threshold::XorShift;
+
+/// The next pseudo-random colour from `rng`.
+pub fn random_color(rng: &mut XorShift) -> Rgb<u8> {
+    let [r, g, b, ..] = rng.next_u64().to_le_bytes();
+    Rgb([r, g, b])
+}
+
+/// `count` pseudo-random colours, the same for the same `seed`.
+pub fn random_colors(count: usize, seed: u64) -> Vec<Rgb<u8>> {
+    let mut rng = XorShift(seed);
+    (0..count).map(|_| random_color(&mut rng)).collect()
+}
+
+/// Red and green ramps across and down, with a blue pattern so neighbouring
+/// pixels differ.
+pub fn gradient(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        Rgb([
+            (x * 255 / width.saturating_sub(1).max(1)) as u8,
+            (y * 255 / height.saturating_sub(1).max(1)) as u8,
+            ((x * 7 + y * 13) % 256) as u8,
+        ])
+    })
+}
+
+/// `gradient` with blue alternating fully on and off, pixel by pixel.
+pub fn checker(width: u32, height: u32) -> RgbImage {
+    let mut img = gradient(width, height);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel[2] = if (x + y) % 2 == 0 { 255 } else { 0 };
+    }
+    img
+}
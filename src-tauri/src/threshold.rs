@@ -0,0 +1,334 @@
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+// Larger maps add nothing visible and cost width * height floats
+const MAX_MAP_SIZE: u32 = 256;
+
+// Side of the built-in blue-noise texture. 64x64 tiles invisibly at pixel-art
+// scales and generates in well under a second.
+const BLUE_NOISE_SIZE: u32 = 64;
+
+// Gaussian sigma from Ulichney's void-and-cluster paper
+const VOID_AND_CLUSTER_SIGMA: f32 = 1.5;
+
+/// A tiling threshold map for ordered dithering. `values` holds `width * height`
+/// thresholds in row-major order, normalized to 0.0..1.0.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThresholdMap {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl ThresholdMap {
+    /// Bayer matrix of `size` x `size`. `size` is rounded up to a power of two
+    /// and clamped to 2..=256; 4 gives the classic 4x4 matrix.
+    pub fn bayer(size: u32) -> Self {
+        let size = size.clamp(2, MAX_MAP_SIZE).next_power_of_two();
+
+        // Recursive construction: M(2n) = [4M + 0, 4M + 2; 4M + 3, 4M + 1]
+        let mut matrix: Vec<u32> = vec![0];
+        let mut n = 1;
+        while n < size {
+            let mut next = vec![0; (4 * n * n) as usize];
+            for y in 0..n {
+                for x in 0..n {
+                    let v = 4 * matrix[(y * n + x) as usize];
+                    next[(y * 2 * n + x) as usize] = v;
+                    next[(y * 2 * n + x + n) as usize] = v + 2;
+                    next[((y + n) * 2 * n + x) as usize] = v + 3;
+                    next[((y + n) * 2 * n + x + n) as usize] = v + 1;
+                }
+            }
+            matrix = next;
+            n *= 2;
+        }
+
+        let levels = (size * size) as f32;
+        Self {
+            width: size,
+            height: size,
+            values: matrix.into_iter().map(|v| v as f32 / levels).collect(),
+        }
+    }
+
+    /// Blue-noise threshold map of `size` x `size` built with Ulichney's
+    /// void-and-cluster method. The result is deterministic, so a given pixel
+    /// always gets the same threshold and video frames don't shimmer.
+    pub fn void_and_cluster(size: u32) -> Self {
+        let size = size.clamp(4, MAX_MAP_SIZE);
+        let area = (size * size) as usize;
+
+        // Toroidal Gaussian lookup, indexed by the wrapped (dx, dy) offset
+        let mut gaussian = vec![0.0f32; area];
+        for dy in 0..size {
+            for dx in 0..size {
+                let wx = dx.min(size - dx) as f32;
+                let wy = dy.min(size - dy) as f32;
+                gaussian[(dy * size + dx) as usize] =
+                    (-(wx * wx + wy * wy) / (2.0 * VOID_AND_CLUSTER_SIGMA * VOID_AND_CLUSTER_SIGMA)).exp();
+            }
+        }
+
+        let mut field = EnergyField { size, gaussian, energy: vec![0.0; area] };
+
+        // Initial binary pattern: ~10% minority pixels from a fixed-seed PRNG
+        let mut pattern = vec![false; area];
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        let initial_ones = (area / 10).max(1);
+        let mut placed = 0;
+        while placed < initial_ones {
+            let idx = (rng.next_u64() % area as u64) as usize;
+            if !pattern[idx] {
+                pattern[idx] = true;
+                field.toggle(idx, 1.0);
+                placed += 1;
+            }
+        }
+
+        // Relax into a well-spread prototype pattern: move the tightest
+        // cluster into the largest void until they coincide (capped, in case
+        // ties make it oscillate).
+        for _ in 0..area {
+            let cluster = field.extreme(&pattern, true, true);
+            pattern[cluster] = false;
+            field.toggle(cluster, -1.0);
+            let void = field.extreme(&pattern, false, false);
+            if void == cluster {
+                pattern[cluster] = true;
+                field.toggle(cluster, 1.0);
+                break;
+            }
+            pattern[void] = true;
+            field.toggle(void, 1.0);
+        }
+
+        let mut ranks = vec![0usize; area];
+
+        // Phase 1: rank the prototype's ones by removing tightest clusters
+        let prototype = pattern.clone();
+        let prototype_energy = field.energy.clone();
+        let mut ones = initial_ones;
+        while ones > 0 {
+            let cluster = field.extreme(&pattern, true, true);
+            pattern[cluster] = false;
+            field.toggle(cluster, -1.0);
+            ones -= 1;
+            ranks[cluster] = ones;
+        }
+
+        // Phase 2: fill the largest voids up to half coverage
+        pattern = prototype;
+        field.energy = prototype_energy;
+        ones = initial_ones;
+        while ones < area / 2 {
+            let void = field.extreme(&pattern, false, false);
+            pattern[void] = true;
+            field.toggle(void, 1.0);
+            ranks[void] = ones;
+            ones += 1;
+        }
+
+        // Phase 3: zeros are now the minority, so track their energy and keep
+        // filling their tightest clusters
+        field.energy = vec![0.0; area];
+        for idx in (0..area).filter(|&idx| !pattern[idx]) {
+            field.toggle(idx, 1.0);
+        }
+        while ones < area {
+            let cluster = field.extreme(&pattern, false, true);
+            pattern[cluster] = true;
+            field.toggle(cluster, -1.0);
+            ranks[cluster] = ones;
+            ones += 1;
+        }
+
+        Self {
+            width: size,
+            height: size,
+            values: ranks.into_iter().map(|r| r as f32 / area as f32).collect(),
+        }
+    }
+
+    /// Uses the gray levels of `img` as thresholds, e.g. a clustered-dot
+    /// halftone cell or a console's dither pattern.
+    pub fn from_image(img: &GrayImage) -> Result<Self, String> {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 {
+            return Err("Threshold map image is empty".to_string());
+        }
+        if width > MAX_MAP_SIZE || height > MAX_MAP_SIZE {
+            return Err(format!("Threshold map is larger than {0}x{0}", MAX_MAP_SIZE));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            // Centre each gray level in its bucket so 0 and 255 aren't special
+            values: img.as_raw().iter().map(|&v| (v as f32 + 0.5) / 256.0).collect(),
+        })
+    }
+
+    /// Loads a threshold map from an image file (typically a grayscale PNG).
+    pub fn load(path: &str) -> Result<Self, String> {
+        let img = image::open(path)
+            .map_err(|e| format!("Failed to open threshold map {}: {}", path, e))?
+            .to_luma8();
+        Self::from_image(&img)
+    }
+
+    /// Threshold for pixel (x, y), tiling the map over the image.
+    pub fn threshold(&self, x: u32, y: u32) -> f32 {
+        let width = self.width.max(1);
+        let height = self.height.max(1);
+        let idx = ((y % height) * width + (x % width)) as usize;
+        // A malformed map (e.g. hand-edited JSON) degrades to no dithering
+        self.values.get(idx).copied().unwrap_or(0.5)
+    }
+}
+
+/// The built-in blue-noise texture, generated on first use and shared afterwards.
+pub fn blue_noise() -> &'static ThresholdMap {
+    static BLUE_NOISE: OnceLock<ThresholdMap> = OnceLock::new();
+    BLUE_NOISE.get_or_init(|| ThresholdMap::void_and_cluster(BLUE_NOISE_SIZE))
+}
+
+// Gaussian-filtered density of the "on" pixels of a toroidal binary pattern
+struct EnergyField {
+    size: u32,
+    gaussian: Vec<f32>,
+    energy: Vec<f32>,
+}
+
+impl EnergyField {
+    // Adds (sign = 1.0) or removes (sign = -1.0) the Gaussian splat of pixel `idx`
+    fn toggle(&mut self, idx: usize, sign: f32) {
+        let size = self.size as usize;
+        let (px, py) = (idx % size, idx / size);
+        for y in 0..size {
+            let dy = (y + size - py) % size;
+            for x in 0..size {
+                let dx = (x + size - px) % size;
+                self.energy[y * size + x] += sign * self.gaussian[dy * size + dx];
+            }
+        }
+    }
+
+    // Index of the highest (`highest`) or lowest energy among pixels whose
+    // pattern value equals `value`
+    fn extreme(&self, pattern: &[bool], value: bool, highest: bool) -> usize {
+        let mut best = usize::MAX;
+        let mut best_energy = if highest { f32::MIN } else { f32::MAX };
+        for (idx, (&on, &e)) in pattern.iter().zip(&self.energy).enumerate() {
+            if on == value && ((highest && e > best_energy) || (!highest && e < best_energy)) {
+                best = idx;
+                best_energy = e;
+            }
+        }
+        best
+    }
+}
+
+/// Small deterministic PRNG (xorshift64) for the initial pattern, so every
+/// build produces the same texture without pulling in a rand dependency. Tests
+/// and benchmarks use it for repeatable data too. The seed must be nonzero.
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each map must use every rank 0..area exactly once
+    fn assert_permutation(map: &ThresholdMap) {
+        let area = (map.width * map.height) as usize;
+        assert_eq!(map.values.len(), area);
+        let mut ranks: Vec<usize> = map.values.iter()
+            .map(|&v| {
+                assert!((0.0..1.0).contains(&v), "{}", v);
+                let rank = v * area as f32;
+                assert!((rank - rank.round()).abs() < 1e-3, "{}", v);
+                rank.round() as usize
+            })
+            .collect();
+        ranks.sort_unstable();
+        assert!(ranks.into_iter().eq(0..area));
+    }
+
+    #[test]
+    fn bayer_matches_the_classic_matrices() {
+        let ranks = |map: ThresholdMap| -> Vec<u32> {
+            let area = (map.width * map.height) as f32;
+            map.values.iter().map(|v| (v * area) as u32).collect()
+        };
+        assert_eq!(ranks(ThresholdMap::bayer(2)), [0, 2, 3, 1]);
+        assert_eq!(ranks(ThresholdMap::bayer(4)), [
+            0, 8, 2, 10,
+            12, 4, 14, 6,
+            3, 11, 1, 9,
+            15, 7, 13, 5,
+        ]);
+        // Rounded up to a power of two
+        assert_eq!(ThresholdMap::bayer(3).width, 4);
+        assert_eq!(ThresholdMap::bayer(0).width, 2);
+    }
+
+    #[test]
+    fn maps_are_rank_permutations() {
+        for size in [2, 4, 8, 16, 64] {
+            assert_permutation(&ThresholdMap::bayer(size));
+        }
+        for size in [4, 8, 13] {
+            assert_permutation(&ThresholdMap::void_and_cluster(size));
+        }
+    }
+
+    #[test]
+    fn void_and_cluster_is_deterministic() {
+        assert_eq!(ThresholdMap::void_and_cluster(8), ThresholdMap::void_and_cluster(8));
+    }
+
+    #[test]
+    fn load_rejects_bad_images() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("pixelforge-threshold-{}-{}", std::process::id(), name));
+
+        let too_wide = path("wide.png");
+        GrayImage::new(MAX_MAP_SIZE + 1, 1).save(&too_wide).unwrap();
+        assert!(ThresholdMap::load(too_wide.to_str().unwrap()).is_err());
+
+        let corrupt = path("corrupt.png");
+        std::fs::write(&corrupt, b"not an image").unwrap();
+        assert!(ThresholdMap::load(corrupt.to_str().unwrap()).is_err());
+
+        let valid = path("valid.png");
+        GrayImage::from_raw(2, 1, vec![0, 255]).unwrap().save(&valid).unwrap();
+        let map = ThresholdMap::load(valid.to_str().unwrap()).unwrap();
+        assert_eq!((map.width, map.height), (2, 1));
+        assert!(map.values.iter().all(|v| (0.0..1.0).contains(v)));
+
+        for file in [too_wide, corrupt, valid] {
+            let _ = std::fs::remove_file(file);
+        }
+        assert!(ThresholdMap::load(path("missing.png").to_str().unwrap()).is_err());
+        assert!(ThresholdMap::from_image(&GrayImage::new(0, 4)).is_err());
+    }
+
+    #[test]
+    fn malformed_maps_disable_dithering() {
+        let map = ThresholdMap { width: 4, height: 4, values: vec![0.1; 3] };
+        assert_eq!(map.threshold(3, 3), 0.5);
+        let empty = ThresholdMap { width: 0, height: 0, values: Vec::new() };
+        assert_eq!(empty.threshold(5, 5), 0.5);
+    }
+}
@@ -0,0 +1,348 @@
+use image::RgbImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{rgb_to_yiq, yiq_to_rgb};
+use crate::effects::noise;
+
+// Lines a VHS recording resolves. Noise, wobble and dropouts work on these
+// rather than on output rows, so they look the same at any output size.
+const TAPE_LINES: f32 = 240.0;
+
+// Tape lines between the knots of the slow wobble
+const WOBBLE_PERIOD: u32 = 16;
+// Tape lines' worth of width per luma noise streak
+const NOISE_STREAK: f32 = 3.0;
+
+// Each setting at 1.0, relative to the frame width (shifts and smears) or
+// height (tracking band)
+const MAX_CHROMA_SHIFT: f32 = 0.005;
+const MAX_CHROMA_BLUR: f32 = 0.015;
+const MAX_LUMA_NOISE: f32 = 0.2;
+const MAX_WOBBLE: f32 = 0.004;
+const MAX_TRACKING_BAND: f32 = 0.08;
+const MAX_TRACKING_SHIFT: f32 = 0.04;
+const MAX_DROPOUT_RATE: f32 = 0.03; // chance per tape line per frame
+
+// Camcorder date stamp: font pixels per tape line, colour and margins
+const STAMP_SCALE: f32 = 1.5;
+const STAMP_COLOR: [u8; 3] = [255, 186, 56];
+const STAMP_MARGIN: f32 = 0.06;
+
+// Independent random streams
+const WOBBLE: u32 = 0;
+const JITTER: u32 = 1;
+const GRAIN: u32 = 2;
+const TRACKING: u32 = 3;
+const DROPOUT: u32 = 4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VhsConfig {
+    pub chroma_shift: f32,      // 0.0 to 1.0, colour lagging to the right of the luma
+    pub chroma_blur: f32,       // 0.0 to 1.0, horizontal colour smear
+    pub luma_noise: f32,        // 0.0 to 1.0, streaky noise on the brightness
+    pub tracking_noise: f32,    // 0.0 to 1.0, head-switching band at the bottom of the frame
+    pub tape_wobble: f32,       // 0.0 to 1.0, per-line horizontal jitter
+    pub dropouts: f32,          // 0.0 to 1.0, how often lost oxide flashes white streaks
+    pub date_stamp: String,     // camcorder date overlay, empty for none
+    pub noise_seed: u64,        // every random effect follows it, so exports are reproducible
+}
+
+impl Default for VhsConfig {
+    fn default() -> Self {
+        Self {
+            chroma_shift: 0.0,
+            chroma_blur: 0.0,
+            luma_noise: 0.0,
+            tracking_noise: 0.0,
+            tape_wobble: 0.0,
+            dropouts: 0.0,
+            date_stamp: String::new(),
+            noise_seed: 0,
+        }
+    }
+}
+
+impl VhsConfig {
+    fn degrades(&self) -> bool {
+        self.chroma_shift > 0.0
+            || self.chroma_blur > 0.0
+            || self.luma_noise > 0.0
+            || self.tracking_noise > 0.0
+            || self.tape_wobble > 0.0
+            || self.dropouts > 0.0
+    }
+
+    // Uniform in 0.0..1.0, the same for the same seed, frame and inputs
+    fn random(&self, stream: u32, a: u32, b: u32, frame: u64) -> f32 {
+        let key = frame ^ self.noise_seed.rotate_left(32) ^ (stream as u64) << 56;
+        (noise(a, b, key) + 1.0) / 2.0
+    }
+}
+
+/// Plays `img` back from a worn VHS tape: the date stamp is recorded first,
+/// then everything goes through the tape's colour, noise and timing faults.
+/// `frame` is the frame's index in the video, so the noise moves.
+pub fn apply_vhs(img: &RgbImage, config: &VhsConfig, frame: u64) -> RgbImage {
+    let (width, height) = img.dimensions();
+    let mut recorded = img.clone();
+    if width == 0 || height == 0 {
+        return recorded;
+    }
+    if !config.date_stamp.trim().is_empty() {
+        draw_date_stamp(&mut recorded, &config.date_stamp);
+    }
+    if !config.degrades() {
+        return recorded;
+    }
+
+    let line_height = height as f32 / TAPE_LINES;
+    let width_f = width as f32;
+    let chroma_shift = config.chroma_shift * MAX_CHROMA_SHIFT * width_f;
+    let chroma_radius = (config.chroma_blur * MAX_CHROMA_BLUR * width_f).round() as usize;
+    let band_height = config.tracking_noise * MAX_TRACKING_BAND * height as f32;
+    let streak_width = NOISE_STREAK * line_height;
+
+    let mut output = RgbImage::new(width, height);
+    output.par_chunks_mut(width as usize * 3).enumerate().for_each(|(y, row)| {
+        let y = y as u32;
+        let line = (y as f32 / line_height) as u32;
+        let source: Vec<[f32; 3]> = (0..width).map(|x| rgb_to_yiq(*recorded.get_pixel(x, y))).collect();
+
+        let mut shift = wobble(config, line, frame) * MAX_WOBBLE * width_f;
+        // Below the head switch the picture drags sideways and breaks up
+        let mut band = 0.0;
+        if band_height > 0.0 && y as f32 >= height as f32 - band_height {
+            band = (y as f32 - (height as f32 - band_height)) / band_height;
+            let tear = 0.5 + 0.5 * config.random(TRACKING, 0, line, frame);
+            shift += band * band * tear * config.tracking_noise * MAX_TRACKING_SHIFT * width_f;
+        }
+
+        let mut luma: Vec<f32> = (0..width).map(|x| sample(&source, x as f32 - shift, 0)).collect();
+        let mut i: Vec<f32> = (0..width).map(|x| sample(&source, x as f32 - shift - chroma_shift, 1)).collect();
+        let mut q: Vec<f32> = (0..width).map(|x| sample(&source, x as f32 - shift - chroma_shift, 2)).collect();
+        if chroma_radius > 0 {
+            i = smear(&smear(&i, chroma_radius), chroma_radius);
+            q = smear(&smear(&q, chroma_radius), chroma_radius);
+        }
+
+        if config.luma_noise > 0.0 || band > 0.0 {
+            // Streaks: noise interpolated along the line, fresh every tape line
+            let amount = config.luma_noise * MAX_LUMA_NOISE + band * config.tracking_noise;
+            for (x, l) in luma.iter_mut().enumerate() {
+                let position = x as f32 / streak_width;
+                let cell = position as u32;
+                let t = position.fract();
+                let a = config.random(GRAIN, cell, line, frame);
+                let b = config.random(GRAIN, cell + 1, line, frame);
+                *l += amount * ((a + (b - a) * t) - 0.5);
+            }
+        }
+
+        if config.dropouts > 0.0 && config.random(DROPOUT, 0, line, frame) < config.dropouts * MAX_DROPOUT_RATE {
+            // A white streak fading out to the right, colour lost under it
+            let start = config.random(DROPOUT, 1, line, frame) * width_f;
+            let length = (0.05 + 0.25 * config.random(DROPOUT, 2, line, frame)) * width_f;
+            for x in start as usize..((start + length) as usize).min(width as usize) {
+                let fade = 1.0 - (x as f32 - start) / length;
+                luma[x] += (1.0 - luma[x]) * 0.9 * fade;
+                i[x] *= 1.0 - fade;
+                q[x] *= 1.0 - fade;
+            }
+        }
+
+        for (x, out) in row.chunks_exact_mut(3).enumerate() {
+            let rgb = yiq_to_rgb([luma[x], i[x], q[x]]);
+            out.copy_from_slice(&rgb.map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8));
+        }
+    });
+
+    output
+}
+
+// Horizontal offset of tape line `line` in -1.0..1.0: a slow drift between
+// knots every WOBBLE_PERIOD lines, plus a little jitter of its own
+fn wobble(config: &VhsConfig, line: u32, frame: u64) -> f32 {
+    if config.tape_wobble <= 0.0 {
+        return 0.0;
+    }
+    let knot = line / WOBBLE_PERIOD;
+    let t = (line % WOBBLE_PERIOD) as f32 / WOBBLE_PERIOD as f32;
+    let t = t * t * (3.0 - 2.0 * t);
+    let a = config.random(WOBBLE, knot, 0, frame);
+    let b = config.random(WOBBLE, knot + 1, 0, frame);
+    let drift = (a + (b - a) * t) * 2.0 - 1.0;
+    let jitter = config.random(JITTER, 0, line, frame) * 2.0 - 1.0;
+    config.tape_wobble * (0.8 * drift + 0.2 * jitter)
+}
+
+// Channel `k` of `row` at the continuous position `x`, linearly interpolated
+// between pixel centres, edges extended
+fn sample(row: &[[f32; 3]], x: f32, k: usize) -> f32 {
+    let last = row.len() as f32 - 1.0;
+    let x = x.clamp(0.0, last);
+    let left = x.floor() as usize;
+    let right = (left + 1).min(row.len() - 1);
+    let t = x - left as f32;
+    row[left][k] + (row[right][k] - row[left][k]) * t
+}
+
+// Mean over a (2 * radius + 1)-pixel window, edges extended
+fn smear(values: &[f32], radius: usize) -> Vec<f32> {
+    let last = values.len() as isize - 1;
+    let at = |i: isize| values[i.clamp(0, last) as usize];
+    let window = (2 * radius + 1) as f32;
+    let mut sum: f32 = (-(radius as isize)..=radius as isize).map(at).sum();
+    let mut output = Vec::with_capacity(values.len());
+    for x in 0..values.len() as isize {
+        output.push(sum / window);
+        sum += at(x + radius as isize + 1) - at(x - radius as isize);
+    }
+    output
+}
+
+// `text` in the bottom-right corner in a blocky camcorder font with a drop
+// shadow, sized to the tape's resolution
+fn draw_date_stamp(img: &mut RgbImage, text: &str) {
+    let (width, height) = img.dimensions();
+    let scale = ((height as f32 / TAPE_LINES * STAMP_SCALE).round() as u32).max(1);
+    let glyphs: Vec<Option<[u8; 7]>> = text.trim().chars().map(|c| glyph(c.to_ascii_uppercase())).collect();
+    let text_width = glyphs.len() as u32 * 6 * scale;
+    let margin_x = (width as f32 * STAMP_MARGIN) as u32;
+    let margin_y = (height as f32 * STAMP_MARGIN) as u32;
+    let left = width.saturating_sub(margin_x + text_width) as i64;
+    let top = height.saturating_sub(margin_y + 7 * scale) as i64;
+
+    for (offset, color) in [(scale as i64, [0, 0, 0]), (0, STAMP_COLOR)] {
+        for (index, glyph) in glyphs.iter().enumerate() {
+            let Some(rows) = glyph else { continue };
+            let char_left = left + (index as u32 * 6 * scale) as i64 + offset;
+            for (gy, bits) in rows.iter().enumerate() {
+                for gx in 0..5 {
+                    if bits & (0b10000 >> gx) == 0 {
+                        continue;
+                    }
+                    let x0 = char_left + (gx * scale) as i64;
+                    let y0 = top + (gy as u32 * scale) as i64 + offset;
+                    for y in y0..y0 + scale as i64 {
+                        for x in x0..x0 + scale as i64 {
+                            if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                                img.get_pixel_mut(x as u32, y as u32).0 = color;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 5x7 glyph rows, most significant of the 5 bits on the left. None draws a
+// blank, e.g. for spaces.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '\'' => [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn gradient() -> RgbImage {
+        test_util::gradient(160, 120)
+    }
+
+    fn worn() -> VhsConfig {
+        VhsConfig {
+            chroma_shift: 0.5,
+            chroma_blur: 0.5,
+            luma_noise: 0.5,
+            tracking_noise: 0.5,
+            tape_wobble: 0.5,
+            dropouts: 1.0,
+            noise_seed: 42,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_and_frame_give_the_same_image() {
+        let img = gradient();
+        let config = worn();
+        assert!(apply_vhs(&img, &config, 7) == apply_vhs(&img, &config, 7));
+        assert!(apply_vhs(&img, &config, 7) != apply_vhs(&img, &config, 8));
+
+        let reseeded = VhsConfig { noise_seed: 43, ..worn() };
+        assert!(apply_vhs(&img, &config, 7) != apply_vhs(&img, &reseeded, 7));
+    }
+
+    #[test]
+    fn zero_intensity_leaves_the_image_alone() {
+        let img = gradient();
+        assert!(apply_vhs(&img, &VhsConfig::default(), 3) == img);
+
+        let still = VhsConfig { tape_wobble: 0.0, tracking_noise: 0.0, noise_seed: 9, ..Default::default() };
+        assert!(apply_vhs(&img, &still, 3) == img);
+        for line in 0..TAPE_LINES as u32 {
+            assert_eq!(wobble(&still, line, 3), 0.0);
+        }
+    }
+
+    #[test]
+    fn wobble_and_tracking_move_with_the_frame() {
+        let img = gradient();
+        for config in [
+            VhsConfig { tape_wobble: 1.0, ..Default::default() },
+            VhsConfig { tracking_noise: 1.0, ..Default::default() },
+        ] {
+            let first = apply_vhs(&img, &config, 0);
+            assert!(first == apply_vhs(&img, &config, 0));
+            assert!(first != apply_vhs(&img, &config, 1));
+        }
+    }
+}
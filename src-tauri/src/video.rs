@@ -0,0 +1,422 @@
+use image::RgbImage;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+
+use crate::dithering::TemporalState;
+use crate::pipeline::Pipeline;
+
+/// Source stream properties read from FFmpeg's banner.
+#[derive(Clone, Copy, Debug)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: Option<f64>,
+    pub duration_sec: Option<f64>,
+}
+
+/// Everything about an export that isn't a per-frame pipeline setting.
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub input_video_path: String,
+    pub output_video_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub total_duration_sec: f64,
+    pub video_speed: f64,     // 0.1 to 4.0 (1.0 = normal)
+    pub interpolation_fps: u32, // 0 = native
+}
+
+// Runs `ffmpeg -i input` with no output. FFmpeg exits with an error in that case,
+// but still prints the stream info we're after on stderr.
+fn read_banner(ffmpeg_path: &str, input_video_path: &str) -> Result<String, String> {
+    let output = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-i", input_video_path])
+        .output()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    Ok(String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// Reads size, frame rate and duration from FFmpeg's stream banner, e.g.
+/// "Duration: 00:00:12.34, ..." and
+/// "Stream #0:0(und): Video: h264 ..., 1920x1080 [SAR 1:1 DAR 16:9], 29.97 fps, ..."
+pub fn probe_video(ffmpeg_path: &str, input_video_path: &str) -> Result<VideoInfo, String> {
+    let banner = read_banner(ffmpeg_path, input_video_path)?;
+
+    let video_line = banner.lines()
+        .find(|line| line.contains("Video:"))
+        .ok_or_else(|| format!("No video stream found in {}", input_video_path))?;
+
+    let (width, height) = video_line.split(',')
+        .flat_map(str::split_whitespace)
+        .find_map(|token| {
+            // Skip codec tags like "0x31637661"
+            let (w, h) = token.split_once('x')?;
+            let (w, h) = (w.parse::<u32>().ok()?, h.parse::<u32>().ok()?);
+            (w > 0 && h > 0).then_some((w, h))
+        })
+        .ok_or_else(|| format!("Could not read frame size of {}", input_video_path))?;
+
+    let frame_rate = video_line.split(',')
+        .map(str::trim)
+        .find_map(|part| part.strip_suffix(" fps"))
+        .and_then(|fps| fps.parse::<f64>().ok())
+        .filter(|fps| *fps > 0.0);
+
+    let duration_sec = banner.lines()
+        .find_map(|line| line.trim().strip_prefix("Duration: "))
+        .and_then(|rest| rest.split(',').next())
+        .and_then(parse_timestamp);
+
+    Ok(VideoInfo { width, height, frame_rate, duration_sec })
+}
+
+/// Decodes `count` frames spread evenly over the video, each scaled to
+/// `width` x `height` with nearest-neighbour sampling like `Pipeline::downscale`.
+/// Fails if FFmpeg reports no duration, since the frames can't be spread then.
+pub fn sample_frames(
+    ffmpeg_path: &str,
+    input_video_path: &str,
+    info: &VideoInfo,
+    count: usize,
+    width: u32,
+    height: u32,
+) -> Result<Vec<RgbImage>, String> {
+    let count = count.max(1);
+    let duration = info.duration_sec
+        .filter(|d| *d > 0.0)
+        .ok_or_else(|| format!("Could not read the duration of {} to sample frames from", input_video_path))?;
+    let scale = format!("scale={}:{}:flags=neighbor", width, height);
+    let mut frames = Vec::with_capacity(count);
+
+    for i in 0..count {
+        // Middle of each of `count` equal stretches
+        let timestamp = (duration * (i as f64 + 0.5) / count as f64).to_string();
+        let output = Command::new(ffmpeg_path)
+            .args([
+                "-nostdin",
+                "-ss", &timestamp,
+                "-i", input_video_path,
+                "-frames:v", "1",
+                "-vf", &scale,
+                "-f", "rawvideo",
+                "-pix_fmt", "rgb24",
+                "pipe:1",
+            ])
+            .output()
+            .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!("FFmpeg failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+        // A seek near the end can come back without a frame; skip it
+        if let Some(frame) = RgbImage::from_raw(width, height, output.stdout) {
+            frames.push(frame);
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(format!("Could not decode any frames of {}", input_video_path));
+    }
+    Ok(frames)
+}
+
+/// `count` frames spread evenly over the video, shrunk to fit within
+/// `max_size` x `max_size` (never enlarged), e.g. to extract a palette from.
+pub fn sample_frames_within(
+    ffmpeg_path: &str,
+    input_video_path: &str,
+    count: usize,
+    max_size: u32,
+) -> Result<Vec<RgbImage>, String> {
+    let info = probe_video(ffmpeg_path, input_video_path)?;
+    let scale = (max_size as f64 / info.width.max(info.height).max(1) as f64).min(1.0);
+    let width = ((info.width as f64 * scale) as u32).max(1);
+    let height = ((info.height as f64 * scale) as u32).max(1);
+    sample_frames(ffmpeg_path, input_video_path, &info, count, width, height)
+}
+
+/// Gives `pipeline` its video-wide automatic palette if its settings ask for
+/// one (`PaletteScope::Video`), from `palette_sample_frames` sampled frames.
+pub fn prepare_sampled_palette(ffmpeg_path: &str, input_video_path: &str, pipeline: &mut Pipeline) -> Result<(), String> {
+    if !pipeline.needs_sampled_palette() {
+        return Ok(());
+    }
+
+    let info = probe_video(ffmpeg_path, input_video_path)?;
+    let (width, height) = pipeline.scaled_dimensions(info.width, info.height);
+    let count = pipeline.config().palette_sample_frames;
+    let frames = sample_frames(ffmpeg_path, input_video_path, &info, count, width, height)?;
+    pipeline.use_sampled_palette(&frames);
+    Ok(())
+}
+
+// "HH:MM:SS.ms" -> seconds
+fn parse_timestamp(time_str: &str) -> Option<f64> {
+    let parts: Vec<&str> = time_str.trim().split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let h = parts[0].parse::<f64>().ok()?;
+    let m = parts[1].parse::<f64>().ok()?;
+    let s = parts[2].parse::<f64>().ok()?;
+    Some(h * 3600.0 + m * 60.0 + s)
+}
+
+// Builds the atempo chain for `video_speed`, respecting atempo's [0.5, 2.0] range.
+fn atempo_filters(video_speed: f64) -> Vec<String> {
+    let mut speed_remaining = video_speed;
+    let mut atempo_filters = Vec::new();
+
+    while speed_remaining > 2.0 {
+        atempo_filters.push("atempo=2.0".to_string());
+        speed_remaining /= 2.0;
+    }
+    while speed_remaining < 0.5 {
+        atempo_filters.push("atempo=0.5".to_string());
+        speed_remaining /= 0.5;
+    }
+    if (speed_remaining - 1.0).abs() > 0.01 {
+        atempo_filters.push(format!("atempo={}", speed_remaining));
+    }
+
+    atempo_filters
+}
+
+// Drains a child's stderr on a background thread so the pipe never fills up,
+// keeping the text around for error reporting.
+fn collect_stderr(child: &mut Child) -> Option<std::thread::JoinHandle<String>> {
+    let mut stderr = child.stderr.take()?;
+    Some(std::thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    }))
+}
+
+fn join_stderr(handle: Option<std::thread::JoinHandle<String>>) -> String {
+    handle.and_then(|h| h.join().ok()).unwrap_or_default()
+}
+
+/// Decodes `input_video_path` to raw RGB frames, runs each one through `pipeline`
+/// and encodes the result to `output_video_path`, copying the (re-timed) audio.
+/// `on_progress` receives a percentage in 0.0..100.0 after every frame.
+pub fn export_video(
+    ffmpeg_path: &str,
+    options: &ExportOptions,
+    pipeline: &Pipeline,
+    mut on_progress: impl FnMut(f64),
+) -> Result<(), String> {
+    let ExportOptions { width, height, video_speed, interpolation_fps, .. } = *options;
+
+    // Frames travel over the pipes as headerless rgb24, so both FFmpeg processes
+    // need an explicit, constant frame rate.
+    let frame_rate = if interpolation_fps > 0 {
+        interpolation_fps as f64
+    } else {
+        probe_video(ffmpeg_path, &options.input_video_path)
+            .ok()
+            .and_then(|info| info.frame_rate)
+            .unwrap_or(30.0)
+    };
+
+    // Decoder filter chain
+    // 1. Speed (PTS)
+    // 2. Interpolation
+    // 3. Constant frame rate, so every frame read from the pipe is one output frame
+    // Downscale / dither / upscale / CRT then happen in Pipeline, exactly like process_frame.
+    let (scaled_width, scaled_height) = pipeline.scaled_dimensions(width, height);
+    let mut filters = Vec::new();
+
+    if interpolation_fps > 0 {
+        // minterpolate is very slow on full-size frames, so interpolate the small
+        // frames instead (Downscale-First) and skip Pipeline::downscale below.
+        filters.push(format!("scale={}:{}:flags=neighbor", scaled_width, scaled_height));
+    } else {
+        // Guard against stale metadata: the pipe reader relies on this exact size
+        filters.push(format!("scale={}:{}:flags=neighbor", width, height));
+    }
+
+    if (video_speed - 1.0).abs() > 0.01 {
+        let pts_factor = 1.0 / video_speed;
+        filters.push(format!("setpts={}*PTS", pts_factor));
+    }
+
+    if interpolation_fps > 0 {
+        filters.push(format!("minterpolate=fps={}:mi_mode=mci:mc_mode=aobmc:me_mode=bidir", interpolation_fps));
+    }
+
+    filters.push(format!("fps={}", frame_rate));
+
+    let (frame_width, frame_height) = if interpolation_fps > 0 {
+        (scaled_width, scaled_height)
+    } else {
+        (width, height)
+    };
+
+    let decoder_args = vec![
+        "-nostdin".to_string(),
+        "-i".to_string(), options.input_video_path.clone(),
+        "-an".to_string(),
+        "-vf".to_string(), filters.join(","),
+        "-f".to_string(), "rawvideo".to_string(),
+        "-pix_fmt".to_string(), "rgb24".to_string(),
+        "pipe:1".to_string(),
+    ];
+
+    // Video comes from our pipe (input 0), audio straight from the source file (input 1)
+    let mut encoder_args = vec![
+        "-y".to_string(),
+        "-f".to_string(), "rawvideo".to_string(),
+        "-pix_fmt".to_string(), "rgb24".to_string(),
+        "-s".to_string(), format!("{}x{}", width, height),
+        "-framerate".to_string(), frame_rate.to_string(),
+        "-i".to_string(), "pipe:0".to_string(),
+        "-i".to_string(), options.input_video_path.clone(),
+        "-map".to_string(), "0:v:0".to_string(),
+        "-map".to_string(), "1:a?".to_string(),
+    ];
+
+    // Audio filter for speed
+    if (video_speed - 1.0).abs() > 0.01 {
+        let atempo = atempo_filters(video_speed);
+        if !atempo.is_empty() {
+            encoder_args.push("-af".to_string());
+            encoder_args.push(atempo.join(","));
+        }
+    }
+
+    encoder_args.extend(vec![
+        "-c:v".to_string(), "libx264".to_string(),
+        "-preset".to_string(), "fast".to_string(),
+        "-crf".to_string(), "18".to_string(),
+        "-pix_fmt".to_string(), "yuv420p".to_string(),
+        "-shortest".to_string(),
+        options.output_video_path.clone(),
+    ]);
+
+    log::debug!("Executing FFmpeg decoder: {} {}", ffmpeg_path, decoder_args.join(" "));
+    log::debug!("Executing FFmpeg encoder: {} {}", ffmpeg_path, encoder_args.join(" "));
+
+    let mut decoder = Command::new(ffmpeg_path)
+        .args(&decoder_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn FFmpeg decoder: {}", e))?;
+
+    let mut encoder = match Command::new(ffmpeg_path)
+        .args(&encoder_args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = decoder.kill();
+            return Err(format!("Failed to spawn FFmpeg encoder: {}", e));
+        }
+    };
+
+    let decoder_stderr = collect_stderr(&mut decoder);
+    let encoder_stderr = collect_stderr(&mut encoder);
+
+    let mut frames_in = decoder.stdout.take().ok_or("Failed to capture decoder stdout")?;
+    let mut frames_out = encoder.stdin.take().ok_or("Failed to capture encoder stdin")?;
+
+    // Calculate expected output frame count for progress reporting
+    let expected_frames = (options.total_duration_sec / video_speed * frame_rate).max(1.0);
+    let frame_size = (frame_width * frame_height * 3) as usize;
+    let mut frame_index: u64 = 0;
+    let mut pipe_error = None;
+    let mut temporal_state = TemporalState::default();
+
+    loop {
+        let mut frame_buf = vec![0u8; frame_size];
+        match frames_in.read_exact(&mut frame_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                pipe_error = Some(format!("Failed to read frame from FFmpeg: {}", e));
+                break;
+            }
+        }
+
+        // frame_buf is exactly frame_width * frame_height * 3 bytes
+        let frame = RgbImage::from_raw(frame_width, frame_height, frame_buf)
+            .expect("frame buffer matches frame dimensions");
+
+        let small = if interpolation_fps > 0 { frame } else { pipeline.downscale(&frame) };
+        let small = pipeline.quantize_temporal(&small, &mut temporal_state);
+        let final_img = pipeline.finish(&small, width, height, frame_index);
+
+        if let Err(e) = frames_out.write_all(final_img.as_raw()) {
+            pipe_error = Some(format!("Failed to write frame to FFmpeg: {}", e));
+            break;
+        }
+
+        frame_index += 1;
+        on_progress((frame_index as f64 / expected_frames * 100.0).min(99.0));
+    }
+
+    // Closing stdin signals end-of-stream to the encoder
+    drop(frames_out);
+    drop(frames_in);
+
+    if pipe_error.is_some() {
+        let _ = decoder.kill();
+    }
+
+    let decoder_status = decoder.wait()
+        .map_err(|e| format!("Failed to wait on FFmpeg decoder: {}", e))?;
+    let encoder_status = encoder.wait()
+        .map_err(|e| format!("Failed to wait on FFmpeg encoder: {}", e))?;
+    let decoder_log = join_stderr(decoder_stderr);
+    let encoder_log = join_stderr(encoder_stderr);
+
+    if !encoder_status.success() {
+        return Err(format!("FFmpeg encoder failed with exit code {:?}: {}", encoder_status.code(), encoder_log));
+    }
+    if let Some(e) = pipe_error {
+        return Err(e);
+    }
+    if !decoder_status.success() {
+        return Err(format!("FFmpeg decoder failed with exit code {:?}: {}", decoder_status.code(), decoder_log));
+    }
+
+    on_progress(100.0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_parse_to_seconds() {
+        assert_eq!(parse_timestamp("00:00:01.50"), Some(1.5));
+        assert_eq!(parse_timestamp(" 01:02:03.25\n"), Some(3723.25));
+        assert_eq!(parse_timestamp("10:00:00.00"), Some(36000.0));
+        for garbage in ["", "N/A", "garbage", "01:02", "1:2:3:4", "aa:bb:cc", "00:00:1.5x"] {
+            assert_eq!(parse_timestamp(garbage), None, "{:?}", garbage);
+        }
+    }
+
+    #[test]
+    fn atempo_stages_stay_in_range_and_multiply_to_the_speed() {
+        for speed in [0.1, 0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0] {
+            let stages: Vec<f64> = atempo_filters(speed).iter()
+                .map(|f| f.strip_prefix("atempo=").and_then(|s| s.parse().ok()).expect(f))
+                .collect();
+            assert!(stages.iter().all(|s| (0.5..=2.0).contains(s)), "{}: {:?}", speed, stages);
+            let total: f64 = stages.iter().product();
+            // A final stage within 1% of 1.0 is left out
+            assert!((total / speed - 1.0).abs() <= 0.01, "{}: {:?}", speed, stages);
+        }
+        assert!(atempo_filters(1.0).is_empty());
+        assert_eq!(atempo_filters(4.0).len(), 2);
+        assert_eq!(atempo_filters(0.1).len(), 4);
+    }
+}
@@ -50,12 +50,12 @@ function App() {
         const result = await invoke('export_video', {
           inputVideoPath: videoMetadata.path,
           outputVideoPath: output,
-          scaleFactor: processingParams.scaleFactor,
           width: videoMetadata.width,
           height: videoMetadata.height,
           totalDurationSec: videoMetadata.duration, // Pass duration
           videoSpeed: processingParams.videoSpeed,
           interpolationFps: processingParams.interpolationFps,
+          config: processingParams, // PipelineConfig: palette, dither & CRT settings
         });
 
         console.log("Export result:", result);
@@ -1,7 +1,51 @@
-import React from 'react';
+import React, { useEffect, useState } from 'react';
 import { useProjectStore } from '../store/useProjectStore';
-import { Sliders, Monitor, Palette, Grid, Upload, FastForward } from 'lucide-react';
-import { open } from '@tauri-apps/plugin-dialog';
+import { Sliders, Monitor, Palette, Grid, Upload, FastForward, Tv } from 'lucide-react';
+import { open, save } from '@tauri-apps/plugin-dialog';
+import { invoke } from '@tauri-apps/api/core';
+import type { ExtractedPalette, LibraryPalette, PaletteFile, PaletteInfo, ThresholdMap } from '../store/useProjectStore';
+
+const PALETTE_EXTENSIONS = ['gpl', 'txt', 'pal', 'act', 'aco', 'hex', 'png'];
+// Library palettes share the palette select with the presets
+const LIBRARY_PREFIX = 'library:';
+
+// Common hardware colour spaces as R,G,B bits per channel
+const BIT_DEPTHS: [string, string][] = [
+    ['1,1,1', 'RGB111 (Teletext)'],
+    ['2,2,2', 'RGB222 (EGA / Master System)'],
+    ['3,3,2', 'RGB332 (8-bit)'],
+    ['3,3,3', 'RGB333 (Mega Drive)'],
+    ['4,4,4', 'RGB444 (Amiga)'],
+    ['5,5,5', 'RGB555 (SNES / GBA)'],
+    ['5,6,5', 'RGB565 (16-bit)'],
+];
+
+type Rgb = [number, number, number];
+
+const toHex = ([r, g, b]: Rgb) =>
+    '#' + [r, g, b].map((c) => c.toString(16).padStart(2, '0')).join('');
+
+const fromHex = (hex: string): Rgb =>
+    [1, 3, 5].map((i) => parseInt(hex.slice(i, i + 2), 16)) as Rgb;
+
+// 0-1 slider of the CRT and VHS sections, shown as a percentage
+const EffectSlider = ({ label, value, onChange }: { label: string; value: number; onChange: (value: number) => void }) => (
+    <div className="space-y-3">
+        <div className="flex justify-between text-xs items-center">
+            <span className="text-zinc-400 font-medium">{label}</span>
+            <span className="bg-emerald-500/10 text-emerald-400 px-2 py-0.5 rounded text-[10px] font-mono border border-emerald-500/20">{Math.round(value * 100)}%</span>
+        </div>
+        <input
+            type="range"
+            min="0"
+            max="1"
+            step="0.05"
+            value={value}
+            onChange={(e) => onChange(parseFloat(e.target.value))}
+            className="w-full h-1.5 bg-zinc-700 rounded-full appearance-none cursor-pointer accent-emerald-500 hover:accent-emerald-400"
+        />
+    </div>
+);
 
 const ControlPanel = () => {
     const {
@@ -12,6 +56,22 @@ const ControlPanel = () => {
         videoMetadata
     } = useProjectStore();
 
+    const [presets, setPresets] = useState<PaletteInfo[]>([]);
+    const [library, setLibrary] = useState<LibraryPalette[]>([]);
+    const libraryPalette = library.find((p) => p.id === processingParams.paletteId) ?? null;
+    // Colour usage of the last palette extracted from the video, by library id
+    const [usage, setUsage] = useState<{ id: string; colors: ExtractedPalette['colors'] } | null>(null);
+    const [isExtracting, setIsExtracting] = useState(false);
+
+    useEffect(() => {
+        invoke<PaletteInfo[]>('list_palettes')
+            .then(setPresets)
+            .catch((err) => console.error("Failed to load palettes", err));
+        invoke<LibraryPalette[]>('list_library_palettes')
+            .then(setLibrary)
+            .catch((err) => console.error("Failed to load palette library", err));
+    }, []);
+
     const handleFileSelect = async () => {
         try {
             const selected = await open({
@@ -31,6 +91,182 @@ const ControlPanel = () => {
         }
     };
 
+    const handleThresholdMapSelect = async () => {
+        try {
+            const selected = await open({
+                multiple: false,
+                filters: [{
+                    name: 'Image',
+                    extensions: ['png', 'bmp', 'gif']
+                }]
+            });
+
+            if (selected && typeof selected === 'string') {
+                const thresholdMap = await invoke<ThresholdMap>('load_threshold_map', { path: selected });
+                updateProcessingParams({ thresholdMap });
+            }
+        } catch (err) {
+            console.error("Failed to load threshold map", err);
+            alert(`加载阈值图失败 (Failed): ${err}`);
+        }
+    };
+
+    const handleBezelSelect = async () => {
+        try {
+            const selected = await open({
+                multiple: false,
+                filters: [{ name: 'Image', extensions: ['png', 'webp'] }]
+            });
+
+            if (selected && typeof selected === 'string') {
+                updateProcessingParams({ bezelImage: selected });
+            }
+        } catch (err) {
+            console.error("Failed to open file dialog", err);
+        }
+    };
+
+    // Adds `palette` to the library (or replaces it there) and selects it
+    const storeLibraryPalette = (palette: LibraryPalette) => {
+        setLibrary((prev) => prev.some((p) => p.id === palette.id)
+            ? prev.map((p) => (p.id === palette.id ? palette : p))
+            : [...prev, palette]);
+        updateProcessingParams({ paletteId: palette.id });
+    };
+
+    const handlePaletteImport = async () => {
+        try {
+            const selected = await open({
+                multiple: false,
+                filters: [{ name: 'Palette', extensions: PALETTE_EXTENSIONS }]
+            });
+
+            if (selected && typeof selected === 'string') {
+                const palette = await invoke<PaletteFile>('import_palette', { path: selected });
+                storeLibraryPalette(await invoke<LibraryPalette>('create_library_palette', { palette }));
+            }
+        } catch (err) {
+            console.error("Failed to import palette", err);
+            alert(`导入调色板失败 (Failed): ${err}`);
+        }
+    };
+
+    const handlePaletteExtract = async () => {
+        if (!videoMetadata) return;
+        const name = prompt('调色板名称 (Name)', `${videoMetadata.path.split(/[\\/]/).pop()} ${processingParams.colorCount}`);
+        if (!name) return;
+        setIsExtracting(true);
+        try {
+            const extracted = await invoke<ExtractedPalette>('extract_palette', {
+                videoPath: videoMetadata.path,
+                frameCount: processingParams.paletteSampleFrames,
+                colorCount: processingParams.colorCount,
+                algorithm: processingParams.paletteAlgorithm,
+                saveAs: name,
+            });
+            if (extracted.saved) {
+                storeLibraryPalette(extracted.saved);
+                setUsage({ id: extracted.saved.id, colors: extracted.colors });
+            }
+        } catch (err) {
+            console.error("Failed to extract palette", err);
+            alert(`提取调色板失败 (Failed): ${err}`);
+        } finally {
+            setIsExtracting(false);
+        }
+    };
+
+    const handlePaletteExport = async () => {
+        const name = libraryPalette?.name ?? processingParams.paletteName;
+        try {
+            const output = await save({
+                filters: [{ name: 'Palette', extensions: PALETTE_EXTENSIONS }],
+                defaultPath: `${name}.gpl`,
+            });
+
+            if (output) {
+                await invoke('export_palette', { path: output, name, config: processingParams });
+            }
+        } catch (err) {
+            console.error("Failed to export palette", err);
+            alert(`导出调色板失败 (Failed): ${err}`);
+        }
+    };
+
+    const handlePaletteRename = async () => {
+        if (!libraryPalette) return;
+        const name = prompt('调色板名称 (Name)', libraryPalette.name);
+        if (!name) return;
+        try {
+            storeLibraryPalette(await invoke<LibraryPalette>('rename_library_palette', { id: libraryPalette.id, name }));
+        } catch (err) {
+            alert(`重命名失败 (Failed): ${err}`);
+        }
+    };
+
+    const handlePaletteDuplicate = async () => {
+        if (!libraryPalette) return;
+        try {
+            storeLibraryPalette(await invoke<LibraryPalette>('duplicate_library_palette', { id: libraryPalette.id }));
+        } catch (err) {
+            alert(`复制失败 (Failed): ${err}`);
+        }
+    };
+
+    const handlePaletteDelete = async () => {
+        if (!libraryPalette || !confirm(`删除调色板 ${libraryPalette.name}？(Delete)`)) return;
+        try {
+            await invoke('delete_library_palette', { id: libraryPalette.id });
+            setLibrary((prev) => prev.filter((p) => p.id !== libraryPalette.id));
+            updateProcessingParams({ paletteId: null });
+        } catch (err) {
+            alert(`删除失败 (Failed): ${err}`);
+        }
+    };
+
+    // Palette utilities on the selected library palette; the result replaces its colours
+    const handlePaletteTool = async (tool: string) => {
+        if (!libraryPalette) return;
+        const other = library.find((p) => p.id === tool.split(':')[1]);
+        try {
+            let result: PaletteFile;
+            if (tool === 'sortLuminance' || tool === 'sortHue') {
+                const order = tool === 'sortHue' ? 'Hue' : 'Luminance';
+                result = await invoke<PaletteFile>('sort_palette', { palette: libraryPalette, order });
+            } else if (tool === 'dedupe') {
+                result = await invoke<PaletteFile>('dedupe_palette', { palette: libraryPalette });
+            } else if (tool === 'subset' && videoMetadata) {
+                result = await invoke<PaletteFile>('subset_palette', {
+                    palette: libraryPalette,
+                    sourcePath: videoMetadata.path,
+                    frameCount: processingParams.paletteSampleFrames,
+                    count: processingParams.colorCount,
+                    colorMetric: processingParams.colorMetric,
+                });
+            } else if (tool.startsWith('merge:') && other) {
+                result = await invoke<PaletteFile>('merge_palettes', { name: libraryPalette.name, palettes: [libraryPalette, other] });
+            } else if (tool.startsWith('remap:') && other) {
+                result = await invoke<PaletteFile>('remap_palette', { from: other, to: libraryPalette, order: 'Luminance' });
+            } else {
+                return;
+            }
+            await handlePaletteEdit(result.colors);
+        } catch (err) {
+            console.error("Palette tool failed", err);
+            alert(`调色板操作失败 (Failed): ${err}`);
+        }
+    };
+
+    const handlePaletteEdit = async (colors: Rgb[]) => {
+        if (!libraryPalette || colors.length === 0) return;
+        try {
+            const palette = { name: libraryPalette.name, colors };
+            storeLibraryPalette(await invoke<LibraryPalette>('edit_library_palette', { id: libraryPalette.id, palette }));
+        } catch (err) {
+            alert(`保存调色板失败 (Failed): ${err}`);
+        }
+    };
+
     return (
         <aside className="w-80 h-full bg-zinc-900/80 rounded-3xl border border-white/5 flex flex-col backdrop-blur-xl z-20 shadow-2xl shadow-black/20 overflow-hidden text-zinc-300">
             <div className="p-6 border-b border-white/5">
@@ -93,15 +329,258 @@ const ControlPanel = () => {
                             <div className="space-y-2">
                                 <label className="text-xs text-zinc-400 block font-medium">调色板 (Palette)</label>
                                 <select
-                                    value={processingParams.paletteName}
-                                    onChange={(e) => updateProcessingParams({ paletteName: e.target.value as any })}
-                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                    value={libraryPalette ? LIBRARY_PREFIX + libraryPalette.id : processingParams.paletteName}
+                                    disabled={processingParams.bitDepth !== null}
+                                    onChange={(e) => e.target.value.startsWith(LIBRARY_PREFIX)
+                                        ? updateProcessingParams({ paletteId: e.target.value.slice(LIBRARY_PREFIX.length) })
+                                        : updateProcessingParams({ paletteName: e.target.value, paletteId: null })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none disabled:opacity-50"
                                 >
                                     <option value="None">自适应 (Auto)</option>
-                                    <option value="GameBoy">GameBoy (4色 绿调)</option>
-                                    <option value="NES">NES (红白机经典)</option>
-                                    <option value="Pico8">PICO-8 (16色幻想)</option>
-                                    <option value="CGA">CGA (复古16色)</option>
+                                    <optgroup label="内置调色板 (Built-in)">
+                                        {presets.map((p) => (
+                                            <option key={p.id} value={p.id} title={p.platform}>
+                                                {p.name} ({p.colorCount}色)
+                                            </option>
+                                        ))}
+                                    </optgroup>
+                                    {library.length > 0 && (
+                                        <optgroup label="我的调色板 (Library)">
+                                            {library.map((p) => (
+                                                <option key={p.id} value={LIBRARY_PREFIX + p.id}>
+                                                    {p.name} ({p.colors.length}色)
+                                                </option>
+                                            ))}
+                                        </optgroup>
+                                    )}
+                                </select>
+                                <div className="flex gap-2">
+                                    <button
+                                        onClick={handlePaletteImport}
+                                        className="flex-1 px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-purple-400 hover:border-purple-500/50 transition-all truncate"
+                                    >
+                                        导入调色板 (Import)
+                                    </button>
+                                    <button
+                                        onClick={handlePaletteExtract}
+                                        disabled={!videoMetadata || isExtracting}
+                                        title="从视频采样帧生成调色板 (Extract from video)"
+                                        className="px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-purple-400 hover:border-purple-500/50 transition-all disabled:opacity-50"
+                                    >
+                                        {isExtracting ? '提取中…' : '提取 (Extract)'}
+                                    </button>
+                                    <button
+                                        onClick={handlePaletteExport}
+                                        disabled={!libraryPalette && processingParams.paletteName === 'None'}
+                                        className="px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-purple-400 hover:border-purple-500/50 transition-all disabled:opacity-50"
+                                    >
+                                        导出 (Export)
+                                    </button>
+                                </div>
+                                {libraryPalette && (
+                                    <div className="space-y-2">
+                                        <div className="flex gap-2">
+                                            {([
+                                                ['重命名 (Rename)', handlePaletteRename],
+                                                ['复制 (Duplicate)', handlePaletteDuplicate],
+                                                ['删除 (Delete)', handlePaletteDelete],
+                                            ] as const).map(([label, onClick]) => (
+                                                <button
+                                                    key={label}
+                                                    onClick={onClick}
+                                                    className="flex-1 px-2 py-1.5 text-[10px] bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-purple-400 hover:border-purple-500/50 transition-all"
+                                                >
+                                                    {label}
+                                                </button>
+                                            ))}
+                                        </div>
+                                        <select
+                                            value=""
+                                            onChange={(e) => handlePaletteTool(e.target.value)}
+                                            className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-1.5 text-[10px] text-zinc-400 focus:outline-none focus:border-purple-500 transition-all appearance-none"
+                                        >
+                                            <option value="">整理调色板… (Tools)</option>
+                                            <option value="sortLuminance">按亮度排序 (Sort by luminance)</option>
+                                            <option value="sortHue">按色相排序 (Sort by hue)</option>
+                                            <option value="dedupe">去除重复色 (Remove duplicates)</option>
+                                            <option value="subset" disabled={!videoMetadata}>
+                                                精简为最适合视频的 {processingParams.colorCount} 色 (Subset)
+                                            </option>
+                                            {library.filter((p) => p.id !== libraryPalette.id).length > 0 && (
+                                                <>
+                                                    <optgroup label="合并 (Merge with)">
+                                                        {library.filter((p) => p.id !== libraryPalette.id).map((p) => (
+                                                            <option key={p.id} value={`merge:${p.id}`}>{p.name}</option>
+                                                        ))}
+                                                    </optgroup>
+                                                    <optgroup label="按亮度对应到 (Remap onto order of)">
+                                                        {library.filter((p) => p.id !== libraryPalette.id).map((p) => (
+                                                            <option key={p.id} value={`remap:${p.id}`}>{p.name}</option>
+                                                        ))}
+                                                    </optgroup>
+                                                </>
+                                            )}
+                                        </select>
+                                        {/* Swatches: click to recolour, right-click to remove */}
+                                        <div className="flex flex-wrap gap-1">
+                                            {libraryPalette.colors.map((color, i) => (
+                                                <input
+                                                    key={i}
+                                                    type="color"
+                                                    value={toHex(color)}
+                                                    onChange={(e) => handlePaletteEdit(libraryPalette.colors.map((c, j) => (j === i ? fromHex(e.target.value) : c)))}
+                                                    onContextMenu={(e) => {
+                                                        e.preventDefault();
+                                                        handlePaletteEdit(libraryPalette.colors.filter((_, j) => j !== i));
+                                                    }}
+                                                    title={toHex(color)}
+                                                    className="w-5 h-5 p-0 border border-zinc-700 rounded cursor-pointer bg-transparent"
+                                                />
+                                            ))}
+                                            <button
+                                                onClick={() => handlePaletteEdit([...libraryPalette.colors, [0, 0, 0]])}
+                                                title="添加颜色 (Add colour)"
+                                                className="w-5 h-5 text-xs leading-none border border-zinc-700 rounded text-zinc-400 hover:text-purple-400 hover:border-purple-500/50"
+                                            >
+                                                +
+                                            </button>
+                                        </div>
+                                        {/* Share of the sampled frames each extracted colour covers */}
+                                        {usage?.id === libraryPalette.id && (
+                                            <div className="flex h-2 rounded overflow-hidden border border-zinc-700">
+                                                {usage.colors.map(({ color, percent }, i) => (
+                                                    <div
+                                                        key={i}
+                                                        style={{ width: `${percent}%`, backgroundColor: toHex(color) }}
+                                                        title={`${toHex(color)} ${percent.toFixed(1)}%`}
+                                                    />
+                                                ))}
+                                            </div>
+                                        )}
+                                    </div>
+                                )}
+                            </div>
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">色彩空间 (Bit Depth)</label>
+                                <select
+                                    value={processingParams.bitDepth
+                                        ? `${processingParams.bitDepth.red},${processingParams.bitDepth.green},${processingParams.bitDepth.blue}`
+                                        : 'None'}
+                                    onChange={(e) => {
+                                        const [red, green, blue] = e.target.value.split(',').map(Number);
+                                        updateProcessingParams({ bitDepth: e.target.value === 'None' ? null : { red, green, blue } });
+                                    }}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                >
+                                    <option value="None">使用调色板 (Palette)</option>
+                                    {BIT_DEPTHS.map(([value, label]) => (
+                                        <option key={value} value={value}>{label}</option>
+                                    ))}
+                                </select>
+                                {processingParams.bitDepth && (
+                                    <div className="space-y-2">
+                                        <div className="flex justify-between text-xs items-center">
+                                            <span className="text-zinc-400 font-medium">同屏色数 (On-screen)</span>
+                                            <span className="text-[10px] font-mono text-purple-400">
+                                                {processingParams.gridColors === 0 ? '全部 (All)' : processingParams.gridColors}
+                                            </span>
+                                        </div>
+                                        <input
+                                            type="range"
+                                            min="0"
+                                            max="256"
+                                            step="1"
+                                            value={processingParams.gridColors}
+                                            onChange={(e) => updateProcessingParams({ gridColors: parseInt(e.target.value) })}
+                                            className="w-full h-1.5 bg-zinc-700 rounded-full appearance-none cursor-pointer accent-purple-500 hover:accent-purple-400"
+                                        />
+                                    </div>
+                                )}
+                            </div>
+
+                            {processingParams.paletteName === 'None' && !processingParams.paletteId && (
+                                <div className="space-y-2">
+                                    <label className="text-xs text-zinc-400 block font-medium">提取算法 (Extraction)</label>
+                                    <select
+                                        value={processingParams.paletteAlgorithm}
+                                        onChange={(e) => updateProcessingParams({ paletteAlgorithm: e.target.value as any })}
+                                        className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                    >
+                                        <option value="NeuQuant">NeuQuant</option>
+                                        <option value="MedianCut">中位切分 (Median Cut)</option>
+                                        <option value="KMeans">K 均值 (K-means, OKLab)</option>
+                                        <option value="Octree">八叉树 (Octree)</option>
+                                        <option value="Wu">Wu 量化 (Wu)</option>
+                                    </select>
+                                    <select
+                                        value={processingParams.paletteScope}
+                                        onChange={(e) => updateProcessingParams({ paletteScope: e.target.value as any })}
+                                        className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                    >
+                                        <option value="Frame">逐帧调色板 (Per frame)</option>
+                                        <option value="Video">整段视频统一调色板 (Whole video)</option>
+                                    </select>
+                                    {/* Pinned colours: click to recolour, right-click to remove */}
+                                    <div className="space-y-1">
+                                        <span className="text-[10px] text-zinc-500">固定颜色 (Pinned Colours)</span>
+                                        <div className="flex flex-wrap gap-1">
+                                            {processingParams.pinnedColors.map((color, i) => (
+                                                <input
+                                                    key={i}
+                                                    type="color"
+                                                    value={toHex(color)}
+                                                    onChange={(e) => updateProcessingParams({
+                                                        pinnedColors: processingParams.pinnedColors.map((c, j) => (j === i ? fromHex(e.target.value) : c)),
+                                                    })}
+                                                    onContextMenu={(e) => {
+                                                        e.preventDefault();
+                                                        updateProcessingParams({ pinnedColors: processingParams.pinnedColors.filter((_, j) => j !== i) });
+                                                    }}
+                                                    title={toHex(color)}
+                                                    className="w-5 h-5 p-0 border border-zinc-700 rounded cursor-pointer bg-transparent"
+                                                />
+                                            ))}
+                                            <button
+                                                onClick={() => updateProcessingParams({ pinnedColors: [...processingParams.pinnedColors, [0, 0, 0]] })}
+                                                title="固定一种颜色 (Pin a colour)"
+                                                className="w-5 h-5 text-xs leading-none border border-zinc-700 rounded text-zinc-400 hover:text-purple-400 hover:border-purple-500/50"
+                                            >
+                                                +
+                                            </button>
+                                        </div>
+                                    </div>
+                                    {processingParams.paletteScope === 'Video' && (
+                                        <div className="space-y-2">
+                                            <div className="flex justify-between text-xs items-center">
+                                                <span className="text-zinc-400 font-medium">采样帧数 (Sampled Frames)</span>
+                                                <span className="text-[10px] font-mono text-purple-400">{processingParams.paletteSampleFrames}</span>
+                                            </div>
+                                            <input
+                                                type="range"
+                                                min="1"
+                                                max="64"
+                                                step="1"
+                                                value={processingParams.paletteSampleFrames}
+                                                onChange={(e) => updateProcessingParams({ paletteSampleFrames: parseInt(e.target.value) })}
+                                                className="w-full h-1.5 bg-zinc-700 rounded-full appearance-none cursor-pointer accent-purple-500 hover:accent-purple-400"
+                                            />
+                                        </div>
+                                    )}
+                                </div>
+                            )}
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">色块限制 (Attribute Clash)</label>
+                                <select
+                                    value={processingParams.attributeMode}
+                                    onChange={(e) => updateProcessingParams({ attributeMode: e.target.value as any })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                >
+                                    <option value="None">无 (None)</option>
+                                    <option value="ZXSpectrum">ZX Spectrum (8x8 每格 2 色)</option>
+                                    <option value="C64Multicolor">C64 多色 (4x8 每格 4 色)</option>
+                                    <option value="NES">NES (16x16 子调色板)</option>
                                 </select>
                             </div>
 
@@ -114,10 +593,90 @@ const ControlPanel = () => {
                                 >
                                     <option value="None">无抖动 (None)</option>
                                     <option value="Ordered">有序抖动 (Bayer)</option>
+                                    <option value="BlueNoise">蓝噪声 (Blue Noise)</option>
                                     <option value="FloydSteinberg">Floyd-Steinberg (扩散)</option>
+                                    <option value="Atkinson">Atkinson (经典 Mac)</option>
+                                    <option value="JarvisJudiceNinke">Jarvis-Judice-Ninke (扩散)</option>
+                                    <option value="Stucki">Stucki (扩散)</option>
+                                    <option value="Burkes">Burkes (扩散)</option>
+                                    <option value="Sierra">Sierra (扩散)</option>
+                                    <option value="TwoRowSierra">Two-Row Sierra (扩散)</option>
+                                    <option value="SierraLite">Sierra Lite (扩散)</option>
                                 </select>
                             </div>
 
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">色彩距离 (Metric)</label>
+                                <select
+                                    value={processingParams.colorMetric}
+                                    onChange={(e) => updateProcessingParams({ colorMetric: e.target.value as any })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none"
+                                >
+                                    <option value="Rgb">RGB (最快)</option>
+                                    <option value="Redmean">Redmean (加权 RGB)</option>
+                                    <option value="LinearRgb">线性 RGB (Linear)</option>
+                                    <option value="Cie76">CIELAB ΔE76</option>
+                                    <option value="Ciede2000">CIEDE2000 (最精确)</option>
+                                    <option value="Oklab">OKLab (感知均匀)</option>
+                                </select>
+                            </div>
+
+                            {processingParams.ditherAlgorithm === 'Ordered' && (
+                                <div className="space-y-2">
+                                    <label className="text-xs text-zinc-400 block font-medium">Bayer 矩阵 (Matrix)</label>
+                                    <div className="flex gap-2">
+                                        <select
+                                            value={processingParams.bayerSize}
+                                            disabled={processingParams.thresholdMap !== null}
+                                            onChange={(e) => updateProcessingParams({ bayerSize: parseInt(e.target.value) })}
+                                            className="flex-1 bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-purple-500 focus:ring-1 focus:ring-purple-500/50 transition-all appearance-none disabled:opacity-50"
+                                        >
+                                            <option value="2">2x2</option>
+                                            <option value="4">4x4</option>
+                                            <option value="8">8x8</option>
+                                            <option value="16">16x16</option>
+                                        </select>
+                                        <button
+                                            onClick={() => processingParams.thresholdMap
+                                                ? updateProcessingParams({ thresholdMap: null })
+                                                : handleThresholdMapSelect()}
+                                            className="px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-purple-400 hover:border-purple-500/50 transition-all"
+                                        >
+                                            {processingParams.thresholdMap
+                                                ? `清除 ${processingParams.thresholdMap.width}x${processingParams.thresholdMap.height}`
+                                                : '自定义阈值图'}
+                                        </button>
+                                    </div>
+                                </div>
+                            )}
+
+                            <label className="flex items-center justify-between text-xs cursor-pointer">
+                                <span className="text-zinc-400 font-medium">蛇形扫描 (Serpentine)</span>
+                                <input
+                                    type="checkbox"
+                                    checked={processingParams.serpentine}
+                                    onChange={(e) => updateProcessingParams({ serpentine: e.target.checked })}
+                                    className="w-4 h-4 accent-purple-500 cursor-pointer"
+                                />
+                            </label>
+
+                            <div className="space-y-3">
+                                <div className="flex justify-between text-xs items-center">
+                                    <span className="text-zinc-400 font-medium">时间稳定 (Temporal)</span>
+                                    <span className="bg-purple-500/10 text-purple-400 px-2 py-0.5 rounded text-[10px] font-mono border border-purple-500/20">{processingParams.temporalThreshold === 0 ? 'OFF' : processingParams.temporalThreshold}</span>
+                                </div>
+                                <input
+                                    type="range"
+                                    min="0"
+                                    max="32"
+                                    step="1"
+                                    value={processingParams.temporalThreshold}
+                                    onChange={(e) => updateProcessingParams({ temporalThreshold: parseInt(e.target.value) })}
+                                    className="w-full h-1.5 bg-zinc-700 rounded-full appearance-none cursor-pointer accent-purple-500 hover:accent-purple-400"
+                                />
+                                <p className="text-[10px] text-zinc-500">保留静止像素的抖动结果，消除视频闪烁。</p>
+                            </div>
+
                             <div className="space-y-3 pt-2">
                                 <div className="flex justify-between text-xs items-center">
                                     <span className="text-zinc-400 font-medium">色彩数量 (Colors)</span>
@@ -147,6 +706,20 @@ const ControlPanel = () => {
                     <div className="space-y-5 bg-zinc-800/30 p-5 rounded-3xl border border-white/5 relative overflow-hidden group hover:border-white/10 transition-colors">
                         <div className="absolute inset-0 bg-gradient-to-br from-emerald-500/5 to-transparent opacity-0 group-hover:opacity-100 transition-opacity" />
 
+                        <div className="relative space-y-2">
+                            <label className="text-xs text-zinc-400 block font-medium">复合视频信号 (Composite Signal)</label>
+                            <select
+                                value={processingParams.composite}
+                                onChange={(e) => updateProcessingParams({ composite: e.target.value as any })}
+                                className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all appearance-none"
+                            >
+                                <option value="None">无 (None)</option>
+                                <option value="NTSC">NTSC</option>
+                                <option value="PAL">PAL</option>
+                                <option value="RF">射频 (RF)</option>
+                            </select>
+                        </div>
+
                         <div className="relative space-y-3">
                             <div className="flex justify-between text-xs items-center">
                                 <span className="text-zinc-400 font-medium">扫描线 (Scanlines)</span>
@@ -162,6 +735,196 @@ const ControlPanel = () => {
                                 className="w-full h-1.5 bg-zinc-700 rounded-full appearance-none cursor-pointer accent-emerald-500 hover:accent-emerald-400"
                             />
                         </div>
+
+                        <div className="relative space-y-4">
+                            {processingParams.scanlineIntensity > 0 && (
+                                <>
+                                    <EffectSlider
+                                        label="扫描线粗细 (Thickness)"
+                                        value={processingParams.scanlineThickness}
+                                        onChange={(scanlineThickness) => updateProcessingParams({ scanlineThickness })}
+                                    />
+                                    <EffectSlider
+                                        label="扫描线柔和度 (Softness)"
+                                        value={processingParams.scanlineSoftness}
+                                        onChange={(scanlineSoftness) => updateProcessingParams({ scanlineSoftness })}
+                                    />
+                                    <div className="space-y-2">
+                                        <label className="text-xs text-zinc-400 block font-medium">每行像素扫描线数 (Lines per Pixel Row)</label>
+                                        <select
+                                            value={processingParams.scanlineCount}
+                                            onChange={(e) => updateProcessingParams({ scanlineCount: parseInt(e.target.value) })}
+                                            className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all appearance-none"
+                                        >
+                                            <option value="1">1</option>
+                                            <option value="2">2</option>
+                                            <option value="3">3</option>
+                                        </select>
+                                    </div>
+                                </>
+                            )}
+                            <EffectSlider
+                                label="光束宽度 (Beam Width)"
+                                value={processingParams.beamWidth}
+                                onChange={(beamWidth) => updateProcessingParams({ beamWidth })}
+                            />
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">荧光粉掩模 (Phosphor Mask)</label>
+                                <select
+                                    value={processingParams.maskType}
+                                    onChange={(e) => updateProcessingParams({ maskType: e.target.value as any })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all appearance-none"
+                                >
+                                    <option value="None">无 (None)</option>
+                                    <option value="ApertureGrille">荫栅 (Aperture Grille)</option>
+                                    <option value="SlotMask">槽形掩模 (Slot Mask)</option>
+                                    <option value="ShadowMask">荫罩 (Shadow Mask)</option>
+                                </select>
+                            </div>
+                            {processingParams.maskType !== 'None' && (
+                                <EffectSlider
+                                    label="掩模强度 (Mask Intensity)"
+                                    value={processingParams.maskIntensity}
+                                    onChange={(maskIntensity) => updateProcessingParams({ maskIntensity })}
+                                />
+                            )}
+
+                            <EffectSlider
+                                label="辉光 (Bloom)"
+                                value={processingParams.bloomIntensity}
+                                onChange={(bloomIntensity) => updateProcessingParams({ bloomIntensity })}
+                            />
+                            <EffectSlider
+                                label="光晕 (Halation)"
+                                value={processingParams.halationIntensity}
+                                onChange={(halationIntensity) => updateProcessingParams({ halationIntensity })}
+                            />
+
+                            <EffectSlider
+                                label="屏幕曲率 (Curvature)"
+                                value={processingParams.curvatureStrength}
+                                onChange={(curvatureStrength) => updateProcessingParams({ curvatureStrength })}
+                            />
+                            {processingParams.curvatureStrength > 0 && (
+                                <div className="space-y-2">
+                                    <label className="text-xs text-zinc-400 block font-medium">曲面采样 (Curvature Filter)</label>
+                                    <select
+                                        value={processingParams.curvatureFilter}
+                                        onChange={(e) => updateProcessingParams({ curvatureFilter: e.target.value as any })}
+                                        className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all appearance-none"
+                                    >
+                                        <option value="Nearest">最近邻 (Nearest)</option>
+                                        <option value="Bilinear">双线性 (Bilinear)</option>
+                                        <option value="Bicubic">双三次 (Bicubic)</option>
+                                    </select>
+                                </div>
+                            )}
+                            <EffectSlider
+                                label="圆角 (Corner Radius)"
+                                value={processingParams.cornerRadius}
+                                onChange={(cornerRadius) => updateProcessingParams({ cornerRadius })}
+                            />
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">边框图片 (Bezel)</label>
+                                <div className="flex gap-2">
+                                    <button
+                                        onClick={handleBezelSelect}
+                                        className="flex-1 px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-emerald-400 hover:border-emerald-500/50 transition-all truncate"
+                                    >
+                                        {processingParams.bezelImage ? processingParams.bezelImage.split(/[\\/]/).pop() : '选择图片 (Choose)'}
+                                    </button>
+                                    {processingParams.bezelImage && (
+                                        <button
+                                            onClick={() => updateProcessingParams({ bezelImage: null })}
+                                            className="px-3 py-2 text-xs bg-zinc-900 border border-zinc-700 rounded-lg text-zinc-400 hover:text-emerald-400 hover:border-emerald-500/50 transition-all"
+                                        >
+                                            清除 (Clear)
+                                        </button>
+                                    )}
+                                </div>
+                            </div>
+
+                            <label className="flex items-center justify-between text-xs cursor-pointer">
+                                <span className="text-zinc-400 font-medium">线性光混合 (Gamma-correct)</span>
+                                <input
+                                    type="checkbox"
+                                    checked={processingParams.gammaCorrect}
+                                    onChange={(e) => updateProcessingParams({ gammaCorrect: e.target.checked })}
+                                    className="w-4 h-4 accent-emerald-500 cursor-pointer"
+                                />
+                            </label>
+                        </div>
+                    </div>
+                </section>
+
+                {/* VHS Section */}
+                <section>
+                    <div className="flex items-center gap-2 mb-4 text-emerald-400">
+                        <Tv className="w-4 h-4" />
+                        <h3 className="text-sm font-bold uppercase tracking-wider">录像带 (VHS)</h3>
+                    </div>
+
+                    <div className="space-y-5 bg-zinc-800/30 p-5 rounded-3xl border border-white/5 relative overflow-hidden group hover:border-white/10 transition-colors">
+                        <div className="absolute inset-0 bg-gradient-to-br from-emerald-500/5 to-transparent opacity-0 group-hover:opacity-100 transition-opacity" />
+
+                        <div className="relative space-y-4">
+                            <EffectSlider
+                                label="色度偏移 (Chroma Shift)"
+                                value={processingParams.chromaShift}
+                                onChange={(chromaShift) => updateProcessingParams({ chromaShift })}
+                            />
+                            <EffectSlider
+                                label="色度模糊 (Chroma Blur)"
+                                value={processingParams.chromaBlur}
+                                onChange={(chromaBlur) => updateProcessingParams({ chromaBlur })}
+                            />
+                            <EffectSlider
+                                label="亮度噪点 (Luma Noise)"
+                                value={processingParams.lumaNoise}
+                                onChange={(lumaNoise) => updateProcessingParams({ lumaNoise })}
+                            />
+                            <EffectSlider
+                                label="循迹噪声 (Tracking Noise)"
+                                value={processingParams.trackingNoise}
+                                onChange={(trackingNoise) => updateProcessingParams({ trackingNoise })}
+                            />
+                            <EffectSlider
+                                label="磁带抖动 (Wobble)"
+                                value={processingParams.tapeWobble}
+                                onChange={(tapeWobble) => updateProcessingParams({ tapeWobble })}
+                            />
+                            <EffectSlider
+                                label="信号丢失 (Dropouts)"
+                                value={processingParams.dropouts}
+                                onChange={(dropouts) => updateProcessingParams({ dropouts })}
+                            />
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">日期水印 (Date Stamp)</label>
+                                <input
+                                    type="text"
+                                    value={processingParams.dateStamp}
+                                    placeholder="JAN. 1 1999"
+                                    onChange={(e) => updateProcessingParams({ dateStamp: e.target.value })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 font-mono focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all"
+                                />
+                            </div>
+
+                            <div className="space-y-2">
+                                <label className="text-xs text-zinc-400 block font-medium">随机种子 (Seed)</label>
+                                <input
+                                    type="number"
+                                    min="0"
+                                    step="1"
+                                    value={processingParams.noiseSeed}
+                                    onChange={(e) => updateProcessingParams({ noiseSeed: Math.max(0, parseInt(e.target.value) || 0) })}
+                                    className="w-full bg-zinc-900 border border-zinc-700 rounded-lg px-3 py-2 text-sm text-zinc-300 font-mono focus:outline-none focus:border-emerald-500 focus:ring-1 focus:ring-emerald-500/50 transition-all"
+                                />
+                                <p className="text-[10px] text-zinc-500">相同的种子导出相同的噪点。</p>
+                            </div>
+                        </div>
                     </div>
                 </section>
 
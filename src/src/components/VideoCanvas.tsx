@@ -23,6 +23,8 @@ const VideoCanvas: React.FC = () => {
     const canvasRef = useRef<HTMLCanvasElement>(null);
     const videoRef = useRef<HTMLVideoElement>(null);
     const requestRef = useRef<number | undefined>(undefined);
+    const previewRequest = useRef(0);
+    const previewShown = useRef(false);
     const [isLoading, setIsLoading] = useState(false);
     const [errorMessage, setErrorMessage] = useState<string | null>(null);
 
@@ -126,6 +128,60 @@ const VideoCanvas: React.FC = () => {
         }
     }, [currentTime, isPlaying]);
 
+    // Processed Preview: run the paused frame through the backend pipeline
+    const processPreview = useCallback(async () => {
+        const video = videoRef.current;
+        if (!video || !videoMetadata || video.readyState < 2 || video.seeking) return;
+
+        const source = document.createElement('canvas');
+        source.width = video.videoWidth;
+        source.height = video.videoHeight;
+        source.getContext('2d')?.drawImage(video, 0, 0);
+        const base64Image = source.toDataURL('image/png').split(',')[1];
+
+        // Only the latest request may draw; older ones are dropped
+        const request = ++previewRequest.current;
+        setIsProcessing(true);
+        try {
+            const result = await invoke<string>('process_frame', {
+                base64Image,
+                videoPath: videoMetadata.path,
+                frameIndex: Math.round(video.currentTime * videoMetadata.fps),
+                config: processingParams, // PipelineConfig: palette, dither & CRT settings
+            });
+            if (request !== previewRequest.current || !video.paused) return;
+
+            const processed = new Image();
+            processed.onload = () => {
+                const canvas = canvasRef.current;
+                const ctx = canvas?.getContext('2d');
+                if (!canvas || !ctx || request !== previewRequest.current || !video.paused) return;
+                canvas.width = processed.width;
+                canvas.height = processed.height;
+                ctx.drawImage(processed, 0, 0);
+                previewShown.current = true;
+            };
+            processed.src = `data:image/png;base64,${result}`;
+        } catch (err) {
+            console.error('Preview processing failed:', err);
+        } finally {
+            if (request === previewRequest.current) setIsProcessing(false);
+        }
+    }, [videoMetadata, processingParams, setIsProcessing]);
+
+    // Re-process when paused on a new frame or when the settings change
+    useEffect(() => {
+        if (isPlaying) {
+            previewRequest.current++;
+            previewShown.current = false;
+            setIsProcessing(false);
+            return;
+        }
+        previewShown.current = false;
+        const timer = setTimeout(processPreview, 150);
+        return () => clearTimeout(timer);
+    }, [currentTime, isPlaying, processPreview, setIsProcessing]);
+
 
     // Animation / Drawing Loop
     const drawLoop = useCallback(() => {
@@ -138,9 +194,9 @@ const VideoCanvas: React.FC = () => {
                 setCurrentTime(video.currentTime);
             }
 
-            // Draw Original Frame continuously
+            // Draw Original Frame continuously, unless a processed preview is up
             const ctx = canvas.getContext('2d');
-            if (ctx && video.readyState >= 2) {
+            if (ctx && video.readyState >= 2 && !previewShown.current) {
                 if (canvas.width !== video.videoWidth || canvas.height !== video.videoHeight) {
                     canvas.width = video.videoWidth;
                     canvas.height = video.videoHeight;
@@ -252,6 +308,7 @@ const VideoCanvas: React.FC = () => {
                     src={blobUrl}
                     className="hidden"
                     onLoadedMetadata={handleLoadedMetadata}
+                    onSeeked={() => { if (videoRef.current?.paused) processPreview(); }}
                     onEnded={() => setIsPlaying(false)}
                     onError={(e) => {
                         console.error("Video element error:", e);
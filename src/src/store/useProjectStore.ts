@@ -8,15 +8,89 @@ export interface VideoMetadata {
     fps: number;
 }
 
+// Custom ordered-dither threshold map, as returned by the `load_threshold_map` command
+export interface ThresholdMap {
+    width: number;
+    height: number;
+    values: number[]; // row-major, 0.0 to 1.0
+}
+
+// Palette read from a file, as returned by the `import_palette` command
+export interface PaletteFile {
+    name: string;
+    colors: [number, number, number][];
+}
+
+// Built-in palette, as returned by the `list_palettes` command
+export interface PaletteInfo {
+    id: string; // value for paletteName
+    name: string;
+    platform: string;
+    colorCount: number;
+}
+
+// Palette saved in the user's library, as returned by the `*_library_palette` commands
+export interface LibraryPalette extends PaletteFile {
+    id: string;
+}
+
+// Palette built by the `extract_palette` command, most used colour first
+export interface ExtractedPalette {
+    colors: { color: [number, number, number]; percent: number }[];
+    saved: LibraryPalette | null; // set when a name to save it under was given
+}
+
+// Hardware colour space, bits per channel
+export interface BitDepth {
+    red: number;
+    green: number;
+    blue: number;
+}
+
 export interface ProcessingParams {
     scaleFactor: number; // 0.1 to 1.0
     colorCount: number; // 2 to 256
-    ditherAlgorithm: 'None' | 'Ordered' | 'FloydSteinberg';
-    paletteName: 'None' | 'GameBoy' | 'NES' | 'CGA' | 'Pico8';
+    paletteAlgorithm: 'NeuQuant' | 'MedianCut' | 'KMeans' | 'Octree' | 'Wu'; // automatic palette extraction
+    paletteScope: 'Frame' | 'Video'; // automatic palette per frame, or one for the whole video
+    paletteSampleFrames: number; // frames sampled for a 'Video' palette
+    pinnedColors: [number, number, number][]; // always part of automatic palettes
+    ditherAlgorithm: 'None' | 'Ordered' | 'BlueNoise' | 'FloydSteinberg' | 'Atkinson' | 'JarvisJudiceNinke'
+        | 'Stucki' | 'Burkes' | 'Sierra' | 'TwoRowSierra' | 'SierraLite';
+    paletteName: string; // 'None' or a PaletteInfo id
+    paletteId: string | null; // library palette, overrides paletteName when set
+    bitDepth: BitDepth | null; // snap to an N-bits-per-channel grid, overrides every palette setting
+    gridColors: number; // with bitDepth: colours on screen at once (0 = whole grid)
+    attributeMode: 'None' | 'ZXSpectrum' | 'C64Multicolor' | 'NES'; // per-cell colour limits (attribute clash)
     ditherStrength: number; // 0.0 to 1.0
+    serpentine: boolean; // alternate scan direction per row (error diffusion)
+    bayerSize: number; // 2, 4, 8, 16 (ordered dithering)
+    thresholdMap: ThresholdMap | null; // overrides bayerSize when set
+    temporalThreshold: number; // 0 to 32, keeps dither decisions of unchanged pixels (0 = off)
+    colorMetric: 'Rgb' | 'Redmean' | 'LinearRgb' | 'Cie76' | 'Ciede2000' | 'Oklab'; // palette matching distance
+    composite: 'None' | 'NTSC' | 'PAL' | 'RF'; // analog video signal before the CRT
+    chromaShift: number; // 0.0 to 1.0, VHS colour lagging behind the picture
+    chromaBlur: number; // 0.0 to 1.0
+    lumaNoise: number; // 0.0 to 1.0
+    trackingNoise: number; // 0.0 to 1.0, head-switching band at the bottom
+    tapeWobble: number; // 0.0 to 1.0, per-line horizontal jitter
+    dropouts: number; // 0.0 to 1.0
+    dateStamp: string; // camcorder date overlay, empty for none
+    noiseSeed: number; // same seed, same VHS noise
     scanlineIntensity: number; // 0.0 to 1.0
+    scanlineThickness: number; // 0.0 to 1.0, share of each line the beam lights
+    scanlineSoftness: number; // 0.0 to 1.0
+    scanlineCount: number; // beams per low-res pixel row
     curvatureStrength: number; // 0.0 to 1.0
+    curvatureFilter: 'Nearest' | 'Bilinear' | 'Bicubic'; // sampling of the curvature warp
+    cornerRadius: number; // 0.0 to 1.0, rounding of the screen corners
+    bezelImage: string | null; // image path drawn over the output (e.g. a TV frame)
     vignetteStrength: number; // 0.0 to 1.0
+    maskType: 'None' | 'ApertureGrille' | 'SlotMask' | 'ShadowMask'; // CRT phosphor layout
+    maskIntensity: number; // 0.0 to 1.0
+    beamWidth: number; // 0.0 to 1.0, bright pixels spreading into the scanline gaps
+    bloomIntensity: number; // 0.0 to 1.0
+    halationIntensity: number; // 0.0 to 1.0
+    gammaCorrect: boolean; // blend CRT effects in linear light
     videoSpeed: number; // 0.1 to 4.0 (1.0 = normal)
     interpolationFps: number; // 0 = native, 30, 60
 }
@@ -42,12 +116,46 @@ export const useProjectStore = create<ProjectState>((set) => ({
     processingParams: {
         scaleFactor: 0.5,
         colorCount: 16,
+        paletteAlgorithm: 'NeuQuant',
+        paletteScope: 'Frame',
+        paletteSampleFrames: 16,
+        pinnedColors: [],
         ditherAlgorithm: 'None',
         paletteName: 'None',
+        paletteId: null,
+        bitDepth: null,
+        gridColors: 0,
+        attributeMode: 'None',
         ditherStrength: 0.5,
+        serpentine: false,
+        bayerSize: 4,
+        thresholdMap: null,
+        temporalThreshold: 0,
+        colorMetric: 'Rgb',
+        composite: 'None',
+        chromaShift: 0.0,
+        chromaBlur: 0.0,
+        lumaNoise: 0.0,
+        trackingNoise: 0.0,
+        tapeWobble: 0.0,
+        dropouts: 0.0,
+        dateStamp: '',
+        noiseSeed: 0,
         scanlineIntensity: 0.0,
+        scanlineThickness: 0.5,
+        scanlineSoftness: 0.5,
+        scanlineCount: 1,
         curvatureStrength: 0.0,
+        curvatureFilter: 'Nearest',
+        cornerRadius: 0.0,
+        bezelImage: null,
         vignetteStrength: 0.0,
+        maskType: 'None',
+        maskIntensity: 0.5,
+        beamWidth: 0.0,
+        bloomIntensity: 0.0,
+        halationIntensity: 0.0,
+        gammaCorrect: false,
         videoSpeed: 1.0,
         interpolationFps: 0,
     },